@@ -6,14 +6,12 @@
     windows_subsystem = "windows"
 )]
 
-use serde_json::{json, Value};
+use serde_json::Value;
 use rusqlite::{Result, Row};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::{Arc, Mutex, atomic::{AtomicBool, Ordering}};
 use tauri::{Manager, Emitter};  
-use tokio;
 use reqwest::Client;
-use quick_xml::de::from_reader;
-use crc32fast;
 
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -28,8 +26,23 @@ struct AppSettings {
     buffer_size: String,
     epg_time_offset: i32,
     epg_refresh_frequency: u32,
+    // How many playlists refresh_all_playlists refreshes at once, overall and per provider host
+    #[serde(default = "default_refresh_concurrency")]
+    refresh_concurrency: usize,
+    #[serde(default = "default_refresh_per_host")]
+    refresh_per_host: usize,
+    // Guide retention: days of past programmes kept for catch-up, and days ahead kept
+    #[serde(default = "default_epg_past_days")]
+    epg_past_days: u32,
+    #[serde(default = "default_epg_future_days")]
+    epg_future_days: u32,
 }
 
+fn default_refresh_concurrency() -> usize { 3 }
+fn default_refresh_per_host() -> usize { 1 }
+fn default_epg_past_days() -> u32 { 7 }
+fn default_epg_future_days() -> u32 { 14 }
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
 #[serde(rename_all = "camelCase")]
 struct Playlist {
@@ -46,6 +59,97 @@ struct Playlist {
     max_connections: Option<i64>,
     expiration_date: Option<String>,
     last_updated: Option<String>,
+    active_connections: Option<i64>,
+    account_status: Option<String>,
+    #[serde(default)]
+    allowed_output_formats: Vec<String>,
+    server_timezone: Option<String>,
+    // Preferred live container ("ts" or "m3u8"); None keeps the provider's default URL
+    output_format: Option<String>,
+    // Alternative server URLs (other DNS names of the same account), tried in order after `url`
+    #[serde(default)]
+    backup_urls: Vec<String>,
+    // The server URL that answered last; it is tried first next time
+    active_url: Option<String>,
+    #[serde(default)]
+    url_failures: Vec<ServerUrlFailure>,
+    #[serde(default)]
+    http_profile: HttpProfile,
+    // Kind of the last refresh failure: "auth", "network", "timeout", "provider" or "parse"
+    error_code: Option<String>,
+}
+
+// How requests for a playlist are made: used by catalogue fetches, EPG downloads and mpv
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Default)]
+#[serde(rename_all = "camelCase", default)]
+struct HttpProfile {
+    // None keeps the built-in desktop browser (or MAG box for Stalker) user agent
+    user_agent: Option<String>,
+    headers: Vec<HttpHeader>,
+    // http://, https://, socks5:// or socks5h:// URL, credentials may be embedded
+    proxy: Option<String>,
+    connect_timeout_secs: Option<u64>,
+    read_timeout_secs: Option<u64>,
+    accept_invalid_certs: bool,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+struct HttpHeader {
+    name: String,
+    value: String,
+}
+
+impl HttpProfile {
+    fn custom_user_agent(&self) -> Option<&str> {
+        self.user_agent.as_deref().map(str::trim).filter(|ua| !ua.is_empty())
+    }
+
+    fn proxy_url(&self) -> Option<&str> {
+        self.proxy.as_deref().map(str::trim).filter(|p| !p.is_empty())
+    }
+
+    // JSON for the http_profile column, NULL while everything is default
+    fn to_column(&self) -> Result<Option<String>, String> {
+        let is_default = self.custom_user_agent().is_none() && self.headers.is_empty() && self.proxy_url().is_none()
+            && self.connect_timeout_secs.is_none() && self.read_timeout_secs.is_none() && !self.accept_invalid_certs;
+        if is_default { return Ok(None); }
+        serde_json::to_string(self).map(Some).map_err(|e| e.to_string())
+    }
+}
+
+// Last failure of one of a playlist's server URLs, cleared once that URL works again
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ServerUrlFailure {
+    url: String,
+    error: String,
+    failed_at: String,
+}
+
+impl Playlist {
+    // The preferred container, as long as the server allows it (an unknown allow-list accepts anything)
+    fn live_output_format(&self) -> Option<&str> {
+        let format = self.output_format.as_deref()?;
+        if self.allowed_output_formats.is_empty() || self.allowed_output_formats.iter().any(|f| f == format) {
+            Some(format)
+        } else {
+            None
+        }
+    }
+
+    // Server URLs in the order they are tried: the last one that worked, then `url`, then the backups
+    fn server_urls(&self) -> Vec<String> {
+        let configured: Vec<&String> = std::iter::once(&self.url).chain(self.backup_urls.iter()).collect();
+        let mut urls: Vec<String> = self.active_url.iter().filter(|a| configured.contains(a)).cloned().collect();
+        for url in configured {
+            if !urls.contains(url) { urls.push(url.clone()); }
+        }
+        urls
+    }
+
+    fn with_url(&self, url: &str) -> Playlist {
+        Playlist { url: url.to_string(), ..self.clone() }
+    }
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -61,6 +165,46 @@ struct NewPlaylistData {
     max_connections: Option<i64>,
     expiration_date: Option<String>,
     last_updated: Option<String>,
+    #[serde(default)]
+    backup_urls: Vec<String>,
+    #[serde(default)]
+    http_profile: HttpProfile,
+}
+
+#[derive(serde::Serialize, Debug, Default)]
+#[serde(rename_all = "camelCase")]
+struct ConnectionTestReport {
+    reachable: bool,
+    authenticated: bool,
+    error: Option<String>,
+    account_status: Option<String>,
+    expiration_date: Option<String>,
+    max_connections: Option<i64>,
+    active_connections: Option<i64>,
+    category_count: usize,
+    channel_count: usize,
+    movie_count: Option<usize>,
+    series_count: Option<usize>,
+}
+
+// What a refresh changed in a playlist's channel list, emitted as `playlist-changes`
+#[derive(serde::Serialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ChannelChangeSummary {
+    playlist_id: i64,
+    added: Vec<String>,
+    removed: Vec<String>,
+    renamed: Vec<RenamedChannel>,
+    updated: usize,
+    unchanged: usize,
+}
+
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RenamedChannel {
+    id: i64,
+    old_name: String,
+    new_name: String,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -80,6 +224,41 @@ struct EpgEntry {
     description: Option<String>,
     start_time: String, 
     end_time: String,   
+    #[serde(flatten)]
+    details: ProgrammeDetails,
+    // Derived from the episode-num values: 1-based numbers and a label like "S02E05"
+    season: Option<i64>,
+    episode: Option<i64>,
+    episode_label: Option<String>,
+}
+
+// The optional XMLTV details of a programme, stored with its EPG entry
+#[derive(serde::Serialize, serde::Deserialize, Debug, Default, Clone)]
+#[serde(rename_all = "camelCase")]
+struct ProgrammeDetails {
+    sub_title: Option<String>,
+    categories: Vec<String>,
+    icon: Option<String>,
+    // Raw episode-num values, e.g. "1.4.0/1" (xmltv_ns, zero-based) and "S02E05" (onscreen)
+    episode_num: Option<String>,
+    episode_onscreen: Option<String>,
+    rating: Option<String>,
+    star_rating: Option<String>,
+    credits: Vec<ProgrammeCredit>,
+    date: Option<String>,
+    is_new: bool,
+    is_premiere: bool,
+    is_previously_shown: bool,
+}
+
+// A person in the <credits> of a programme: director, actor, writer, presenter, ...
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+struct ProgrammeCredit {
+    role: String,
+    name: String,
+    // The part an actor plays, when the guide names it
+    character: Option<String>,
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug)]
@@ -88,13 +267,18 @@ struct Channel {
     id: i64,
     playlist_id: i64,
     name: String,
-    logo_url: String,
+    // None when the provider has no logo for the channel
+    logo_url: Option<String>,
     stream_url: String,
     epg: Vec<EpgEntry>, 
     category: String,
     category_id: Option<i64>,
     is_favorite: bool,
     is_hidden: bool,
+    catchup_days: i64,
+    channel_number: Option<i64>,
+    // Minutes this channel's guide is moved by
+    epg_shift: i64,
 }
 
 #[derive(serde::Deserialize, Debug)]
@@ -118,51 +302,103 @@ struct PaginatedResponse<T> {
     total: i64,
 }
 
-// --- XMLTV STRUCTS (Updated for Fallback Support) ---
-#[derive(serde::Deserialize, Debug)]
-struct Tv {
-    #[serde(rename = "channel", default)]
-    channels: Vec<XmlChannelDef>, 
-    #[serde(rename = "programme", default)]
-    programmes: Vec<Programme>,
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VodItem {
+    id: i64,
+    playlist_id: i64,
+    title: String,
+    stream_url: String,
+    image_url: String,
+    r#type: String,
+    description: Option<String>,
+    release_year: Option<i64>,
+    genres: Vec<String>,
+    duration: Option<i64>,
+    rating: Option<f64>,
+    added_at: Option<String>,
+    is_favorite: bool,
+    is_on_watchlist: bool,
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct XmlChannelDef {
-    #[serde(rename = "@id")]
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Episode {
+    id: i64,
+    series_id: i64,
+    season_number: i64,
+    episode_number: i64,
+    title: String,
+    description: Option<String>,
+    image_url: String,
+    stream_url: String,
+    duration: Option<i64>,
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Season {
+    id: i64,
+    series_id: i64,
+    season_number: i64,
+    name: Option<String>,
+    image_url: Option<String>,
+    episodes: Vec<Episode>,
+}
+
+#[derive(serde::Serialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct VodCategory {
     id: String,
-    #[serde(rename = "display-name", default)]
-    display_names: Vec<DisplayName>,
+    name: String,
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct DisplayName {
-    #[serde(rename = "$value", default)]
-    value: String,
+// --- XMLTV STRUCTS (Updated for Fallback Support) ---
+// Guides are read event by event (see GuideParser), so only one channel or programme
+// is held in memory at a time
+#[derive(Debug)]
+enum GuideEvent {
+    Channel { id: String, display_name: Option<String> },
+    Programme(Box<GuideProgramme>),
 }
 
-#[derive(serde::Deserialize, Debug)]
-struct Programme {
-    #[serde(rename = "@start")]
+#[derive(Debug, Default)]
+struct GuideProgramme {
     start: String,
-    #[serde(rename = "@stop")]
     stop: String,
-    #[serde(rename = "@channel")]
-    channel: String, 
-    title: Title,
-    desc: Option<Desc>,
+    channel: String,
+    title: String,
+    desc: Option<String>,
+    details: ProgrammeDetails,
 }
-#[derive(serde::Deserialize, Debug)]
-struct Title {
-    #[serde(rename = "$value", default)]
-    value: String,
+
+// Text element of a <channel> or <programme> being read. Categories and credits collect
+// every occurrence; of the others only the first is kept.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GuideField {
+    DisplayName,
+    Title,
+    SubTitle,
+    Desc,
+    Category,
+    EpisodeNum,
+    Rating,
+    StarRating,
+    Credit,
+    Date,
 }
-#[derive(serde::Deserialize, Debug)]
-struct Desc {
-    #[serde(rename = "$value", default)]
-    value: String,
+
+// Programme elements whose <value> or children are read as a field
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GuideContext {
+    Rating,
+    StarRating,
+    Credits,
 }
 
+// Child elements of <credits>; anything else in there (an actor's <image>, ...) is ignored
+const XMLTV_CREDIT_ROLES: [&str; 10] = ["director", "actor", "writer", "adapter", "producer", "composer", "editor", "presenter", "commentator", "guest"];
+
 // --- Helper to sanitize names for fuzzy matching ---
 fn sanitize_name(name: &str) -> String {
     let mut clean = name.to_lowercase();
@@ -217,873 +453,4139 @@ struct XtreamCategory {
 #[derive(serde::Deserialize, Debug)]
 struct XtreamLiveStream {
     stream_id: serde_json::Value, 
+    #[serde(default)] num: serde_json::Value,
     #[serde(default)] name: String,
     #[serde(default)] stream_icon: Option<String>,
     #[serde(default)] category_id: Option<String>,
     #[serde(default)] epg_channel_id: Option<String>,
+    #[serde(default)] tv_archive: serde_json::Value,
+    #[serde(default)] tv_archive_duration: serde_json::Value,
 }
 
-fn update_playlist_status(conn: &rusqlite::Connection, playlist_id: i64, status: &str, error_message: Option<String>) -> Result<(), String> {
-    let now = chrono::Utc::now().to_rfc3339();
-    conn.execute(
-        "UPDATE playlists SET status = ?1, last_updated = ?2, error_message = ?3 WHERE id = ?4",
-        rusqlite::params![status, now, error_message, playlist_id],
-    ).map_err(|e| e.to_string())?;
-    Ok(())
+#[derive(serde::Deserialize, Debug)]
+struct XtreamVodStream {
+    stream_id: serde_json::Value,
+    #[serde(default)] name: String,
+    #[serde(default)] stream_icon: Option<String>,
+    #[serde(default)] rating: serde_json::Value,
+    #[serde(default)] added: serde_json::Value,
+    #[serde(default)] year: serde_json::Value,
+    #[serde(default)] plot: Option<String>,
+    #[serde(default)] category_id: serde_json::Value,
+    #[serde(default)] container_extension: Option<String>,
 }
 
-fn map_row_to_playlist(row: &Row) -> rusqlite::Result<Playlist> {
-    Ok(Playlist {
-        id: row.get(0)?,
-        name: row.get(1)?,
-        url: row.get(2)?,
-        r#type: row.get(3)?,
-        is_active: row.get(4)?,
-        status: row.get(5)?,
-        error_message: row.get(6)?,
-        username: row.get(7)?,
-        password: row.get(8)?,
-        mac_address: row.get(9)?,
-        max_connections: row.get(10)?,
-        expiration_date: row.get(11)?,
-        last_updated: row.get(12)?,
-    })
+#[derive(serde::Deserialize, Debug)]
+struct XtreamSeries {
+    series_id: serde_json::Value,
+    #[serde(default)] name: String,
+    #[serde(default)] cover: Option<String>,
+    #[serde(default)] plot: Option<String>,
+    #[serde(default)] genre: Option<String>,
+    #[serde(default, rename = "releaseDate")] release_date: Option<String>,
+    #[serde(default)] rating: serde_json::Value,
+    #[serde(default)] last_modified: serde_json::Value,
+    #[serde(default)] category_id: serde_json::Value,
 }
 
-fn get_playlist_by_id(conn: &rusqlite::Connection, id: i64) -> Option<Playlist> {
-    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").ok()?;
-    stmt.query_row([id], map_row_to_playlist).ok()
+#[derive(serde::Deserialize, Debug)]
+struct XtreamSeason {
+    #[serde(default)] season_number: serde_json::Value,
+    #[serde(default)] name: Option<String>,
+    #[serde(default)] cover: Option<String>,
 }
 
-fn get_db_connection(app: &tauri::AppHandle) -> Result<rusqlite::Connection, String> {
-    let path = app.path()
-        .app_data_dir()
-        .map_err(|e| e.to_string())? 
-        .join("rebootv.db");
-    rusqlite::Connection::open(path)
-        .map_err(|e| e.to_string())
+#[derive(serde::Deserialize, Debug)]
+struct XtreamEpisode {
+    id: serde_json::Value,
+    #[serde(default)] episode_num: serde_json::Value,
+    #[serde(default)] season: serde_json::Value,
+    #[serde(default)] title: String,
+    #[serde(default)] container_extension: Option<String>,
+    // Panels send an empty array instead of an object when there is no info
+    #[serde(default)] info: serde_json::Value,
 }
 
-#[tauri::command]
-fn initialize_database(app: tauri::AppHandle) -> Result<(), String> {
-    let conn = get_db_connection(&app)?;
-    conn.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT)", []).map_err(|e| e.to_string())?;
-
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS playlists (
-            id                INTEGER PRIMARY KEY AUTOINCREMENT,
-            name              TEXT NOT NULL,
-            url               TEXT NOT NULL,
-            type              TEXT NOT NULL,
-            is_active         BOOLEAN NOT NULL DEFAULT true,
-            status            TEXT NOT NULL DEFAULT 'inactive',
-            error_message     TEXT,
-            username          TEXT,
-            password          TEXT,
-            mac_address       TEXT,
-            max_connections   INTEGER,
-            expiration_date   TEXT,
-            last_updated      TEXT
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+#[derive(serde::Deserialize, Debug, Default)]
+struct XtreamSeriesInfo {
+    #[serde(default)] seasons: Vec<XtreamSeason>,
+    // Either { "1": [..], "2": [..] } or [[..], [..]] depending on the panel
+    #[serde(default)] episodes: serde_json::Value,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS categories (
-            id            INTEGER PRIMARY KEY AUTOINCREMENT,
-            playlist_id   INTEGER NOT NULL,
-            name          TEXT NOT NULL,
-            is_hidden     BOOLEAN NOT NULL DEFAULT false,
-            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+#[derive(serde::Deserialize, Debug, Default)]
+struct XtreamUserInfo {
+    #[serde(default)] auth: serde_json::Value,
+    #[serde(default)] status: Option<String>,
+    #[serde(default)] message: Option<String>,
+    #[serde(default)] exp_date: serde_json::Value,
+    #[serde(default)] max_connections: serde_json::Value,
+    #[serde(default)] active_cons: serde_json::Value,
+    #[serde(default)] allowed_output_formats: Vec<String>,
+}
 
-    let _ = conn.execute("ALTER TABLE channels ADD COLUMN sort_order INTEGER DEFAULT 0", []);
+#[derive(serde::Deserialize, Debug, Default)]
+struct XtreamServerInfo {
+    #[serde(default)] timezone: Option<String>,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS channels (
-            id            INTEGER PRIMARY KEY AUTOINCREMENT,
-            playlist_id   INTEGER NOT NULL,
-            name          TEXT NOT NULL,
-            logo_url      TEXT,
-            stream_url    TEXT NOT NULL,
-            category      TEXT,
-            category_id   INTEGER,
-            epg_channel_id TEXT,
-            is_favorite   BOOLEAN NOT NULL DEFAULT false,
-            is_hidden     BOOLEAN NOT NULL DEFAULT false,
-            sort_order    INTEGER NOT NULL DEFAULT 0,
-            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
-            FOREIGN KEY(category_id) REFERENCES categories(id) ON DELETE SET NULL
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+// Response of player_api.php without an action
+#[derive(serde::Deserialize, Debug, Default)]
+struct XtreamAccountInfo {
+    #[serde(default)] user_info: XtreamUserInfo,
+    #[serde(default)] server_info: XtreamServerInfo,
+}
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS epg_entries (
-            id            INTEGER PRIMARY KEY, 
-            channel_id    INTEGER NOT NULL,
-            title         TEXT NOT NULL,
-            description   TEXT,
-            start_time    TEXT NOT NULL, 
-            end_time      TEXT NOT NULL, 
-            FOREIGN KEY(channel_id) REFERENCES channels(id) ON DELETE CASCADE
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+impl XtreamAccountInfo {
+    fn expiration_date(&self) -> Option<String> {
+        json_i64(&self.user_info.exp_date)
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.to_rfc3339())
+    }
 
-    conn.execute(
-        "CREATE TABLE IF NOT EXISTS recently_watched (
-            channel_id    INTEGER PRIMARY KEY,
-            last_watched  TEXT NOT NULL,
-            FOREIGN KEY(channel_id) REFERENCES channels(id) ON DELETE CASCADE
-        )",
-        [],
-    ).map_err(|e| e.to_string())?;
+    // A readable error for accounts the provider will not serve, None when usable
+    fn status_error(&self) -> Option<RefreshError> {
+        if json_i64(&self.user_info.auth) == Some(0) {
+            return Some(ErrorKind::Auth.error("invalid username or password"));
+        }
+        let status = self.user_info.status.as_deref().unwrap_or("Active");
+        let detail = self.user_info.message.as_deref().filter(|m| !m.is_empty()).map(|m| format!(" ({})", m)).unwrap_or_default();
+        match status.to_lowercase().as_str() {
+            "expired" => Some(match self.expiration_date() {
+                Some(date) => ErrorKind::Auth.error(format!("account expired on {}{}", &date[..10], detail)),
+                None => ErrorKind::Auth.error(format!("account expired{}", detail)),
+            }),
+            "banned" => Some(ErrorKind::Auth.error(format!("account banned by provider{}", detail))),
+            "disabled" => Some(ErrorKind::Auth.error(format!("account disabled by provider{}", detail))),
+            _ => None,
+        }
+    }
+}
 
-    // --- NEW: INDICES FOR PERFORMANCE ---
-    // This drastically speeds up 'DELETE FROM channels' because SQLite 
-    // doesn't have to scan the whole epg table for every channel deletion.
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_epg_channel_id ON epg_entries(channel_id)", []).map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_epg_time ON epg_entries(start_time, end_time)", []).map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_playlist_id ON channels(playlist_id)", []).map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_category_id ON channels(category_id)", []).map_err(|e| e.to_string())?;
-    conn.execute("CREATE INDEX IF NOT EXISTS idx_categories_playlist_id ON categories(playlist_id)", []).map_err(|e| e.to_string())?;
+// Base URL and credentials of an Xtream Codes account, normalised from the playlist row
+struct XtreamAccount {
+    base_url: String,
+    username: String,
+    password: String,
+}
 
-    Ok(())
+// Server base as the Xtream URLs are built on it: trimmed, without player_api.php, ending in '/'
+fn xtream_base_url(url: &str) -> String {
+    let mut base_url = url.trim().to_string();
+    if base_url.ends_with("player_api.php") { base_url = base_url.replace("player_api.php", ""); }
+    if !base_url.ends_with('/') { base_url.push('/'); }
+    base_url
 }
 
+impl XtreamAccount {
+    fn from_playlist(playlist: &Playlist) -> Result<Self, RefreshError> {
+        let (username, password) = match (&playlist.username, &playlist.password) {
+            (Some(u), Some(p)) => (u.trim().to_string(), p.trim().to_string()),
+            _ => return Err(ErrorKind::Auth.error("missing username or password")),
+        };
+        Ok(XtreamAccount { base_url: xtream_base_url(&playlist.url), username, password })
+    }
 
-#[tauri::command]
-fn get_playlists(app: tauri::AppHandle) -> Result<Vec<Playlist>, String> {
-    let conn = get_db_connection(&app)?;
-    let mut stmt = conn.prepare("SELECT * FROM playlists").map_err(|e| e.to_string())?;
-    let playlists = stmt.query_map([], map_row_to_playlist)
-        .map_err(|e| e.to_string())?
-        .collect::<Result<Vec<Playlist>, _>>()
-        .map_err(|e| e.to_string())?;
-    Ok(playlists)
+    fn account_url(&self) -> String {
+        format!("{}player_api.php?username={}&password={}", self.base_url, self.username, self.password)
+    }
+
+    fn action_url(&self, action: &str) -> String {
+        format!("{}player_api.php?username={}&password={}&action={}", self.base_url, self.username, self.password, action)
+    }
+
+    fn live_url(&self, stream_id: i64, format: Option<&str>) -> String {
+        match format {
+            Some(ext) => format!("{}live/{}/{}/{}.{}", self.base_url, self.username, self.password, stream_id, ext),
+            None => format!("{}{}/{}/{}", self.base_url, self.username, self.password, stream_id),
+        }
+    }
+
+    fn movie_url(&self, stream_id: i64, extension: Option<&str>) -> String {
+        let ext = extension.filter(|e| !e.is_empty()).unwrap_or("mp4");
+        format!("{}movie/{}/{}/{}.{}", self.base_url, self.username, self.password, stream_id, ext)
+    }
+
+    // `start` is "YYYY-MM-DD:HH-MM" in the server's timezone, `duration` in minutes
+    fn timeshift_url(&self, stream_id: i64, start: &str, duration: i64, php_style: bool) -> String {
+        if php_style {
+            format!("{}streaming/timeshift.php?username={}&password={}&stream={}&start={}&duration={}", self.base_url, self.username, self.password, stream_id, start, duration)
+        } else {
+            format!("{}timeshift/{}/{}/{}/{}/{}.ts", self.base_url, self.username, self.password, duration, start, stream_id)
+        }
+    }
+
+    fn episode_url(&self, episode_id: i64, extension: Option<&str>) -> String {
+        let ext = extension.filter(|e| !e.is_empty()).unwrap_or("mp4");
+        format!("{}series/{}/{}/{}.{}", self.base_url, self.username, self.password, episode_id, ext)
+    }
 }
 
-#[tauri::command]
-fn schedule_notification(_payload: Value) -> Result<(), String> { Ok(()) }
+// Recognises the links providers hand out for an Xtream account:
+//   http://host:port/get.php?username=U&password=P&type=m3u_plus
+//   http://host:port/player_api.php?username=U&password=P
+//   http://host:port/xmltv.php?username=U&password=P
+//   http://host:port/live/U/P/123.ts   (also /movie/.. and /series/..)
+// and returns (server base with trailing '/', username, password).
+fn parse_xtream_link(link: &str) -> Option<(String, String, String)> {
+    let url = reqwest::Url::parse(link.trim()).ok()?;
+    if url.scheme() != "http" && url.scheme() != "https" { return None; }
+    let origin = url.origin().ascii_serialization();
+    let segments: Vec<&str> = url.path_segments()?.collect();
 
-#[tauri::command]
-fn get_settings(app: tauri::AppHandle) -> Result<Option<AppSettings>, String> {
-    let conn = get_db_connection(&app)?;
-    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = 'app_settings'").map_err(|e| e.to_string())?;
-    match stmt.query_row([], |row| row.get::<_, String>(0)) {
-        Ok(json_value) => {
-            let settings: AppSettings = serde_json::from_str(&json_value).map_err(|e| e.to_string())?;
-            Ok(Some(settings))
-        },
-        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
-        Err(e) => Err(e.to_string()),
+    if let Some(script) = segments.last() {
+        if ["get.php", "player_api.php", "xmltv.php"].contains(script) {
+            let query: HashMap<String, String> = url.query_pairs().into_owned().collect();
+            let username = query.get("username").filter(|u| !u.is_empty())?.clone();
+            let password = query.get("password").filter(|p| !p.is_empty())?.clone();
+            let prefix: String = segments[..segments.len() - 1].iter().map(|s| format!("{}/", s)).collect();
+            return Some((format!("{}/{}", origin, prefix), username, password));
+        }
     }
+
+    // Stream URL form: [prefix/]{live|movie|series}/U/P/id.ext
+    let kind_pos = segments.iter().rposition(|s| ["live", "movie", "series"].contains(s))?;
+    if segments.len() != kind_pos + 4 { return None; }
+    let (username, password) = (segments[kind_pos + 1], segments[kind_pos + 2]);
+    if username.is_empty() || password.is_empty() { return None; }
+    let prefix: String = segments[..kind_pos].iter().map(|s| format!("{}/", s)).collect();
+    Some((format!("{}/{}", origin, prefix), username.to_string(), password.to_string()))
 }
-#[tauri::command]
+
+// get.php / player_api.php links only exist on Xtream servers
+fn is_xtream_api_link(link: &str) -> bool {
+    reqwest::Url::parse(link.trim()).ok().is_some_and(|url| {
+        url.path_segments().and_then(|mut segments| segments.next_back()).is_some_and(|script| script == "get.php" || script == "player_api.php")
+    })
+}
+
+// Turns a pasted provider link into a normalised Xtream playlist (base URL + credentials).
+// Credentials typed into their own fields win over the ones embedded in the link.
+// An M3U playlist is only converted for an Xtream API link: a plain M3U can be hosted on a
+// path that merely looks like an Xtream stream URL.
+fn normalize_playlist_source(r#type: &mut String, url: &mut String, username: &mut Option<String>, password: &mut Option<String>) {
+    *url = url.trim().to_string();
+    if r#type == "stalker" { return; }
+    if r#type != "xtream" && !is_xtream_api_link(url) { return; }
+    let Some((base, link_user, link_pass)) = parse_xtream_link(url) else { return };
+
+    let is_blank = |v: &Option<String>| !matches!(v.as_deref(), Some(s) if !s.trim().is_empty());
+    if is_blank(username) { *username = Some(link_user); }
+    if is_blank(password) { *password = Some(link_pass); }
+    *url = base;
+    *r#type = "xtream".to_string();
+}
+
+// Cleans the backup server list like `url`: pasted Xtream links are reduced to their server base,
+// blanks and duplicates (of each other or of the primary URL) are dropped
+fn normalize_backup_urls(r#type: &str, url: &str, backup_urls: &mut Vec<String>) {
+    let mut cleaned: Vec<String> = Vec::new();
+    for backup in backup_urls.iter() {
+        let mut backup = backup.trim().to_string();
+        if r#type == "xtream" {
+            if let Some((base, _, _)) = parse_xtream_link(&backup) { backup = base; }
+        }
+        if !backup.is_empty() && backup != url && !cleaned.contains(&backup) { cleaned.push(backup); }
+    }
+    *backup_urls = cleaned;
+}
+
+// Backup URLs are stored newline separated, NULL when there are none
+fn join_backup_urls(backup_urls: &[String]) -> Option<String> {
+    if backup_urls.is_empty() { None } else { Some(backup_urls.join("\n")) }
+}
+
+// Live category as reported by the source; M3U has no category ids, so the group title stands in
+#[derive(Debug)]
+struct LiveCategory {
+    remote_id: String,
+    name: String,
+}
+
+// Provider-agnostic channel row produced by every live source (Xtream, M3U, ...)
+#[derive(Debug)]
+struct LiveChannelEntry {
+    // The provider's id for the channel, only unique within its playlist
+    remote_id: i64,
+    name: String,
+    logo_url: Option<String>,
+    stream_url: String,
+    category: String,
+    // Provider id of the category, matched against LiveCategory::remote_id
+    category_remote_id: Option<String>,
+    epg_channel_id: Option<String>,
+    sort_order: i64,
+    // Provider channel number (Xtream `num`, M3U `tvg-chno`, Stalker `number`)
+    channel_number: Option<i64>,
+    // Days of TV archive (catch-up) the provider keeps, 0 when unsupported
+    catchup_days: i64,
+    // Guide shift in minutes when the playlist sets one (M3U `tvg-shift`); None keeps the stored value
+    epg_shift: Option<i64>,
+}
+
+// --- M3U / M3U8 PARSING ---
+#[derive(Debug, Default)]
+struct M3uEntry {
+    title: String,
+    attributes: HashMap<String, String>,
+    group: Option<String>,
+    url: String,
+}
+
+// Splits the body of an #EXTINF line (everything after "#EXTINF:") into its
+// key="value" attributes and the display title after the first unquoted comma.
+fn parse_extinf(body: &str) -> (HashMap<String, String>, String) {
+    let mut attributes = HashMap::new();
+    let chars: Vec<char> = body.chars().collect();
+    let mut i = 0;
+
+    // Skip the duration ("-1", "0", ...)
+    while i < chars.len() && chars[i] != ' ' && chars[i] != ',' { i += 1; }
+
+    loop {
+        while i < chars.len() && chars[i] == ' ' { i += 1; }
+        if i >= chars.len() { return (attributes, String::new()); }
+        if chars[i] == ',' { break; }
+
+        let key_start = i;
+        while i < chars.len() && chars[i] != '=' && chars[i] != ' ' && chars[i] != ',' { i += 1; }
+        let key: String = chars[key_start..i].iter().collect();
+
+        if i < chars.len() && chars[i] == '=' {
+            i += 1;
+            let value: String = if i < chars.len() && chars[i] == '"' {
+                i += 1;
+                let value_start = i;
+                while i < chars.len() && chars[i] != '"' { i += 1; }
+                let v = chars[value_start..i].iter().collect();
+                i += 1; // closing quote
+                v
+            } else {
+                let value_start = i;
+                while i < chars.len() && chars[i] != ' ' && chars[i] != ',' { i += 1; }
+                chars[value_start..i].iter().collect()
+            };
+            attributes.insert(key.to_lowercase(), value.trim().to_string());
+        }
+    }
+
+    let title: String = chars[(i + 1).min(chars.len())..].iter().collect();
+    (attributes, title.trim().to_string())
+}
+
+fn parse_m3u(content: &str) -> Result<Vec<M3uEntry>, RefreshError> {
+    let content = content.trim_start_matches('\u{feff}');
+    let mut lines = content.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).peekable();
+
+    match lines.peek() {
+        Some(first) if first.starts_with("#EXTM3U") => { lines.next(); },
+        _ => return Err(ErrorKind::Parse.error("not a valid M3U playlist (missing #EXTM3U header)")),
+    }
+
+    let mut entries = Vec::new();
+    let mut current: Option<M3uEntry> = None;
+
+    for line in lines {
+        if let Some(body) = line.strip_prefix("#EXTINF:") {
+            let (attributes, title) = parse_extinf(body);
+            current = Some(M3uEntry { title, attributes, ..Default::default() });
+        } else if let Some(group) = line.strip_prefix("#EXTGRP:") {
+            if let Some(entry) = current.as_mut() { entry.group = Some(group.trim().to_string()); }
+        } else if line.starts_with('#') {
+            // Other directives (#EXTVLCOPT, #KODIPROP, ...) are ignored
+            continue;
+        } else if let Some(mut entry) = current.take() {
+            entry.url = line.to_string();
+            entries.push(entry);
+        }
+    }
+
+    Ok(entries)
+}
+
+// M3U entries have no provider id, so derive a stable one from the playlist and stream URL.
+// A URL listed again (the same feed in another group or under another name) or a hash collision
+// adds the group and name to the key, then a counter, so every listing keeps its own channel.
+fn m3u_remote_id(used: &mut HashSet<i64>, playlist_id: i64, url: &str, category: &str, name: &str) -> i64 {
+    let hash = |key: &str| i64::from(crc32fast::hash(key.as_bytes()));
+    let id = hash(&format!("{}-{}", playlist_id, url));
+    if used.insert(id) { return id; }
+    let key = format!("{}-{}-{}-{}", playlist_id, url, category, name);
+    let mut id = hash(&key);
+    let mut occurrence = 1;
+    while !used.insert(id) {
+        occurrence += 1;
+        id = hash(&format!("{}#{}", key, occurrence));
+    }
+    id
+}
+
+async fn fetch_m3u_live(client: &Client, playlist: &Playlist, task: &RefreshTask) -> Result<(Vec<LiveCategory>, Vec<LiveChannelEntry>), RefreshError> {
+    let source = playlist.url.trim();
+    let content = if source.starts_with("http://") || source.starts_with("https://") {
+        let resp = send_with_retry(client.get(source)).await?;
+        String::from_utf8_lossy(&read_body(resp, task, "live").await?).into_owned()
+    } else {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        tokio::fs::read_to_string(path).await.map_err(|e| ErrorKind::Provider.error(format!("cannot read {}: {}", path, e)))?
+    };
+
+    task.progress("live", "parse", 0, None);
+    let parsed = parse_m3u(&content)?;
+
+    let mut categories: Vec<LiveCategory> = Vec::new();
+    let mut entries = Vec::with_capacity(parsed.len());
+    let mut used_ids: HashSet<i64> = HashSet::new();
+    for (index, item) in parsed.into_iter().enumerate() {
+        let attr = |key: &str| item.attributes.get(key).filter(|v| !v.is_empty()).cloned();
+
+        let category = attr("group-title").or_else(|| item.group.clone()).unwrap_or_else(|| "Uncategorized".to_string());
+        if !categories.iter().any(|c| c.name == category) {
+            categories.push(LiveCategory { remote_id: category.clone(), name: category.clone() });
+        }
+
+        let name = if item.title.is_empty() { attr("tvg-name").unwrap_or_else(|| item.url.clone()) } else { item.title.clone() };
+
+        let id = m3u_remote_id(&mut used_ids, playlist.id, &item.url, &category, &name);
+
+        entries.push(LiveChannelEntry {
+            remote_id: id,
+            name,
+            logo_url: attr("tvg-logo"),
+            stream_url: item.url,
+            category_remote_id: Some(category.clone()),
+            category,
+            epg_channel_id: attr("tvg-id"),
+            sort_order: index as i64,
+            channel_number: attr("tvg-chno").and_then(|n| n.trim().parse::<i64>().ok()),
+            catchup_days: 0,
+            // tvg-shift is in hours and may be fractional ("-1.5", "+2")
+            epg_shift: attr("tvg-shift").and_then(|h| h.trim().trim_start_matches('+').parse::<f64>().ok()).map(|h| (h * 60.0).round() as i64),
+        });
+    }
+
+    Ok((categories, entries))
+}
+
+// Providers send ids and counters either as JSON numbers or as strings
+fn json_i64(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) => n.as_i64(),
+        Value::String(s) => s.trim().parse::<i64>().ok(),
+        _ => None,
+    }
+}
+
+fn json_string(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+fn json_f64(value: &Value) -> Option<f64> {
+    match value {
+        Value::Number(n) => n.as_f64(),
+        Value::String(s) => s.trim().parse::<f64>().ok(),
+        _ => None,
+    }
+}
+
+// Pulls a plausible release year out of titles like "Movie (2019)" or "Movie - 2019"
+fn extract_year(title: &str) -> Option<i64> {
+    let bytes = title.as_bytes();
+    let mut found = None;
+    for i in 0..bytes.len().saturating_sub(3) {
+        let window = &bytes[i..i + 4];
+        let bounded = (i == 0 || !bytes[i - 1].is_ascii_digit()) && !bytes.get(i + 4).is_some_and(|b| b.is_ascii_digit());
+        if bounded && window.iter().all(|b| b.is_ascii_digit()) {
+            let year: i64 = title[i..i + 4].parse().ok()?;
+            if (1900..=2100).contains(&year) { found = Some(year); }
+        }
+    }
+    found
+}
+
+// --- STALKER / MAG PORTAL ---
+const STALKER_USER_AGENT: &str = "Mozilla/5.0 (QtEmbedded; U; Linux; C) AppleWebKit/533.3 (KHTML, like Gecko) MAG200 stbapp ver: 2 rev: 250 Safari/533.3";
+
+#[derive(serde::Deserialize, Debug)]
+struct StalkerGenre {
+    #[serde(default)] id: Value,
+    #[serde(default)] title: String,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StalkerChannel {
+    #[serde(default)] id: Value,
+    #[serde(default)] name: String,
+    #[serde(default)] number: Value,
+    #[serde(default)] cmd: String,
+    #[serde(default)] logo: Option<String>,
+    #[serde(default)] tv_genre_id: Value,
+    #[serde(default)] xmltv_id: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug)]
+struct StalkerChannelPage {
+    #[serde(default)] total_items: Value,
+    #[serde(default)] max_page_items: Value,
+    #[serde(default)] data: Vec<StalkerChannel>,
+}
+
+struct StalkerSession {
+    client: Client,
+    origin: String,
+    api_url: String,
+    portal_url: String,
+    mac: String,
+    token: String,
+    // A custom user agent from the playlist's HTTP profile replaces the MAG one
+    user_agent: String,
+}
+
+impl StalkerSession {
+    // Performs the handshake and activates the token with get_profile
+    async fn connect(client: &Client, playlist: &Playlist) -> Result<Self, RefreshError> {
+        let mac = match playlist.mac_address.as_deref().map(str::trim) {
+            Some(m) if !m.is_empty() => m.to_uppercase(),
+            _ => return Err(ErrorKind::Auth.error("missing MAC address")),
+        };
+
+        let portal_url = playlist.url.trim().to_string();
+        let parsed = reqwest::Url::parse(&portal_url).map_err(|e| ErrorKind::Network.error(format!("invalid portal URL: {}", e)))?;
+        let origin = parsed.origin().ascii_serialization();
+        let api_url = if parsed.path().contains("stalker_portal") {
+            format!("{}/stalker_portal/server/load.php", origin)
+        } else {
+            format!("{}/portal.php", origin)
+        };
+
+        let user_agent = playlist.http_profile.custom_user_agent().unwrap_or(STALKER_USER_AGENT).to_string();
+        let mut session = StalkerSession { client: client.clone(), origin, api_url, portal_url, mac, token: String::new(), user_agent };
+
+        let handshake = session.request(&[("type", "stb"), ("action", "handshake"), ("token", "")]).await?;
+        session.token = handshake.get("token").and_then(|t| t.as_str()).unwrap_or_default().to_string();
+        if session.token.is_empty() {
+            return Err(ErrorKind::Auth.error("portal handshake returned no token"));
+        }
+
+        let profile = session.request(&[("type", "stb"), ("action", "get_profile")]).await?;
+        if matches!(profile.get("id"), None | Some(Value::Null)) {
+            return Err(ErrorKind::Auth.error("portal rejected the MAC address"));
+        }
+
+        Ok(session)
+    }
+
+    async fn request(&self, params: &[(&str, &str)]) -> Result<Value, RefreshError> {
+        let mut req = self.client.get(&self.api_url)
+            .query(params)
+            .query(&[("JsHttpRequest", "1-xml")])
+            .header(reqwest::header::USER_AGENT, &self.user_agent)
+            .header("X-User-Agent", "Model: MAG250; Link: WiFi")
+            .header(reqwest::header::REFERER, &self.portal_url)
+            .header(reqwest::header::COOKIE, format!("mac={}; stb_lang=en; timezone=UTC", self.mac));
+        if !self.token.is_empty() {
+            req = req.header(reqwest::header::AUTHORIZATION, format!("Bearer {}", self.token));
+        }
+
+        let resp = send_with_retry(req).await?;
+        let body = resp.text().await.map_err(request_error)?;
+        let mut parsed: Value = serde_json::from_str(&body).map_err(|e| ErrorKind::Parse.error(format!("portal JSON: {}", e)))?;
+        Ok(parsed.get_mut("js").map(Value::take).unwrap_or(Value::Null))
+    }
+
+    async fn get_genres(&self) -> Result<Vec<StalkerGenre>, RefreshError> {
+        let js = self.request(&[("type", "itv"), ("action", "get_genres")]).await?;
+        serde_json::from_value(js).map_err(|e| ErrorKind::Parse.error(format!("portal genres: {}", e)))
+    }
+
+    async fn get_channels(&self, task: &RefreshTask) -> Result<Vec<StalkerChannel>, RefreshError> {
+        let mut channels = Vec::new();
+        let mut page = 1;
+        let mut first_page: Option<usize> = None;
+        let mut previous_first_id = None;
+        loop {
+            let page_str = page.to_string();
+            let js = self.request(&[
+                ("type", "itv"), ("action", "get_ordered_list"), ("genre", "*"),
+                ("fav", "0"), ("sortby", "number"), ("p", &page_str),
+            ]).await?;
+            let chunk: StalkerChannelPage = serde_json::from_value(js).map_err(|e| ErrorKind::Parse.error(format!("portal channel list: {}", e)))?;
+
+            let total = json_i64(&chunk.total_items).and_then(|t| usize::try_from(t).ok()).filter(|t| *t > 0);
+            let per_page = json_i64(&chunk.max_page_items).and_then(|n| usize::try_from(n).ok()).filter(|n| *n > 0);
+            let received = chunk.data.len();
+            // A portal that ignores the page parameter keeps answering with the same page
+            let first_id = chunk.data.first().map(|c| json_string(&c.id));
+            if first_id.is_some() && first_id == previous_first_id { break; }
+            previous_first_id = first_id;
+            let first_page_len = *first_page.get_or_insert(received);
+            channels.extend(chunk.data);
+            task.progress("live", "download", channels.len(), total);
+            task.check_cancelled()?;
+
+            if stalker_last_page(received, first_page_len, per_page, total, channels.len()) { break; }
+            page += 1;
+        }
+        Ok(channels)
+    }
+
+    // Exchanges a channel `cmd` for the short-lived playable URL
+    async fn create_link(&self, cmd: &str) -> Result<String, RefreshError> {
+        let js = self.request(&[("type", "itv"), ("action", "create_link"), ("cmd", cmd), ("series", ""), ("forced_storage", "undefined"), ("disable_ad", "0"), ("download", "0")]).await?;
+        let link = js.get("cmd").and_then(|c| c.as_str()).unwrap_or_default();
+        let link = link.strip_prefix("ffmpeg ").unwrap_or(link).trim();
+        if link.is_empty() {
+            return Err(ErrorKind::Provider.error("portal did not return a stream link"));
+        }
+        Ok(link.to_string())
+    }
+}
+
+// Portals report the page size and channel total, but some leave either out. Without them the
+// list ends at an empty page or one shorter than the first.
+fn stalker_last_page(received: usize, first_page_len: usize, per_page: Option<usize>, total: Option<usize>, collected: usize) -> bool {
+    if received == 0 { return true; }
+    match total {
+        Some(total) => collected >= total,
+        None => received < per_page.unwrap_or(first_page_len),
+    }
+}
+
+async fn fetch_stalker_live(client: &Client, playlist: &Playlist, task: &RefreshTask) -> Result<(Vec<LiveCategory>, Vec<LiveChannelEntry>), RefreshError> {
+    let session = StalkerSession::connect(client, playlist).await?;
+
+    let genres = session.get_genres().await?;
+    let genre_map: HashMap<String, String> = genres.iter()
+        .filter(|g| json_string(&g.id) != "*")
+        .map(|g| (json_string(&g.id), g.title.clone()))
+        .collect();
+
+    let channels = session.get_channels(task).await?;
+
+    let mut entries = Vec::with_capacity(channels.len());
+    for (index, chan) in channels.into_iter().enumerate() {
+        let id = match json_i64(&chan.id) { Some(id) if id != 0 => id, _ => continue };
+        if chan.cmd.is_empty() { continue; }
+
+        let genre_id = json_string(&chan.tv_genre_id);
+        let category = genre_map.get(&genre_id).cloned().unwrap_or_else(|| "Uncategorized".to_string());
+        let logo_url = chan.logo.filter(|l| !l.is_empty()).map(|l| {
+            if l.starts_with("http") { l } else { format!("{}/{}", session.origin, l.trim_start_matches('/')) }
+        });
+
+        entries.push(LiveChannelEntry {
+            remote_id: id,
+            name: chan.name,
+            logo_url,
+            stream_url: chan.cmd,
+            category_remote_id: genre_map.contains_key(&genre_id).then_some(genre_id),
+            category,
+            epg_channel_id: chan.xmltv_id.filter(|x| !x.is_empty()),
+            sort_order: index as i64,
+            channel_number: json_i64(&chan.number),
+            catchup_days: 0,
+            epg_shift: None,
+        });
+    }
+
+    let categories = genres.into_iter()
+        .filter(|g| json_string(&g.id) != "*")
+        .map(|g| LiveCategory { remote_id: json_string(&g.id), name: g.title })
+        .collect();
+    Ok((categories, entries))
+}
+
+// The error message and its code are stored together, or cleared together
+fn update_playlist_status(conn: &rusqlite::Connection, playlist_id: i64, status: &str, error: Option<&RefreshError>) -> Result<(), String> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let (error_message, error_code) = (error.map(|e| e.message.as_str()), error.map(|e| e.kind.code()));
+    conn.execute(
+        "UPDATE playlists SET status = ?1, last_updated = ?2, error_message = ?3, error_code = ?4 WHERE id = ?5",
+        rusqlite::params![status, now, error_message, error_code, playlist_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Puts the playlist into the error state, notifies the UI and hands the message back for `Err(..)`
+fn fail_refresh(conn: &rusqlite::Connection, app: &tauri::AppHandle, playlist_id: i64, error: RefreshError) -> RefreshError {
+    let _ = update_playlist_status(conn, playlist_id, "error", Some(&error));
+    let _ = app.emit("playlist-update", get_playlist_by_id(conn, playlist_id).unwrap_or_default());
+    error
+}
+
+fn map_row_to_playlist(row: &Row) -> rusqlite::Result<Playlist> {
+    Ok(Playlist {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        url: row.get(2)?,
+        r#type: row.get(3)?,
+        is_active: row.get(4)?,
+        status: row.get(5)?,
+        error_message: row.get(6)?,
+        username: row.get(7)?,
+        password: row.get(8)?,
+        mac_address: row.get(9)?,
+        max_connections: row.get(10)?,
+        expiration_date: row.get(11)?,
+        last_updated: row.get(12)?,
+        active_connections: row.get(13)?,
+        account_status: row.get(14)?,
+        allowed_output_formats: row.get::<_, Option<String>>(15)?
+            .map(|f| f.split(',').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+        server_timezone: row.get(16)?,
+        output_format: row.get(17)?,
+        backup_urls: row.get::<_, Option<String>>(18)?
+            .map(|u| u.lines().map(|s| s.to_string()).collect())
+            .unwrap_or_default(),
+        active_url: row.get(19)?,
+        url_failures: row.get::<_, Option<String>>(20)?
+            .and_then(|f| serde_json::from_str(&f).ok())
+            .unwrap_or_default(),
+        http_profile: row.get::<_, Option<String>>(21)?
+            .and_then(|p| serde_json::from_str(&p).ok())
+            .unwrap_or_default(),
+        error_code: row.get(22)?,
+    })
+}
+
+fn get_playlist_by_id(conn: &rusqlite::Connection, id: i64) -> Option<Playlist> {
+    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").ok()?;
+    stmt.query_row([id], map_row_to_playlist).ok()
+}
+
+// Columns read by map_row_to_epg_entry, in order
+const EPG_ENTRY_COLUMNS: &str = "id, title, description, start_time, end_time, sub_title, categories, icon, episode_num, episode_onscreen, rating, star_rating, credits, date, is_new, is_premiere, is_previously_shown";
+
+// `first` is the position of the id column, for queries that select something before it
+fn map_row_to_epg_entry(row: &Row, first: usize) -> rusqlite::Result<EpgEntry> {
+    let details = ProgrammeDetails {
+        sub_title: row.get(first + 5)?,
+        categories: row.get::<_, Option<String>>(first + 6)?.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+        icon: row.get(first + 7)?,
+        episode_num: row.get(first + 8)?,
+        episode_onscreen: row.get(first + 9)?,
+        rating: row.get(first + 10)?,
+        star_rating: row.get(first + 11)?,
+        credits: row.get::<_, Option<String>>(first + 12)?.and_then(|json| serde_json::from_str(&json).ok()).unwrap_or_default(),
+        date: row.get(first + 13)?,
+        is_new: row.get(first + 14)?,
+        is_premiere: row.get(first + 15)?,
+        is_previously_shown: row.get(first + 16)?,
+    };
+    let (season, episode) = details.episode_num.as_deref().map_or((None, None), parse_xmltv_ns);
+    Ok(EpgEntry {
+        id: row.get(first)?,
+        title: row.get(first + 1)?,
+        description: row.get(first + 2)?,
+        start_time: row.get(first + 3)?,
+        end_time: row.get(first + 4)?,
+        episode_label: episode_label(season, episode, details.episode_onscreen.as_deref()),
+        season,
+        episode,
+        details,
+    })
+}
+
+// xmltv_ns is "season.episode.part", each zero-based and optionally "n/total"; any part may be empty
+fn parse_xmltv_ns(value: &str) -> (Option<i64>, Option<i64>) {
+    let mut parts = value.split('.').map(|part| {
+        part.split('/').next().and_then(|n| n.trim().parse::<i64>().ok()).filter(|n| *n >= 0).map(|n| n + 1)
+    });
+    (parts.next().flatten(), parts.next().flatten())
+}
+
+// "S02E05" when the guide numbers the episode, else whatever it prints on screen
+fn episode_label(season: Option<i64>, episode: Option<i64>, onscreen: Option<&str>) -> Option<String> {
+    match (season, episode) {
+        (Some(season), Some(episode)) => Some(format!("S{:02}E{:02}", season, episode)),
+        (None, Some(episode)) => Some(format!("E{:02}", episode)),
+        _ => onscreen.map(str::trim).filter(|label| !label.is_empty()).map(|label| label.to_string()),
+    }
+}
+
+fn get_db_connection(app: &tauri::AppHandle) -> Result<rusqlite::Connection, String> {
+    let path = app.path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())? 
+        .join("rebootv.db");
+    rusqlite::Connection::open(path)
+        .map_err(|e| e.to_string())
+}
+
+#[tauri::command]
+fn initialize_database(app: tauri::AppHandle) -> Result<(), String> {
+    let conn = get_db_connection(&app)?;
+    create_schema(&conn)
+}
+
+// Creates and migrates every table; also used for the scratch database of the import benchmark
+fn create_schema(conn: &rusqlite::Connection) -> Result<(), String> {
+    conn.execute("CREATE TABLE IF NOT EXISTS settings (key TEXT PRIMARY KEY, value TEXT)", []).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS playlists (
+            id                INTEGER PRIMARY KEY AUTOINCREMENT,
+            name              TEXT NOT NULL,
+            url               TEXT NOT NULL,
+            type              TEXT NOT NULL,
+            is_active         BOOLEAN NOT NULL DEFAULT true,
+            status            TEXT NOT NULL DEFAULT 'inactive',
+            error_message     TEXT,
+            username          TEXT,
+            password          TEXT,
+            mac_address       TEXT,
+            max_connections   INTEGER,
+            expiration_date   TEXT,
+            last_updated      TEXT,
+            active_connections INTEGER,
+            account_status    TEXT,
+            allowed_output_formats TEXT,
+            server_timezone   TEXT,
+            output_format     TEXT,
+            backup_urls       TEXT,
+            active_url        TEXT,
+            url_failures      TEXT,
+            http_profile      TEXT,
+            error_code        TEXT
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    // Account info columns (appended, so map_row_to_playlist indices stay valid on old databases)
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN active_connections INTEGER", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN account_status TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN allowed_output_formats TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN server_timezone TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN output_format TEXT", []);
+    // Server failover: newline separated backups, the last working URL and per-URL failures as JSON
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN backup_urls TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN active_url TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN url_failures TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN http_profile TEXT", []);
+    let _ = conn.execute("ALTER TABLE playlists ADD COLUMN error_code TEXT", []);
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS categories (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id   INTEGER NOT NULL,
+            name          TEXT NOT NULL,
+            is_hidden     BOOLEAN NOT NULL DEFAULT false,
+            remote_id     TEXT,
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // Provider category id, so a category keeps its local id and hidden flag across refreshes
+    let _ = conn.execute("ALTER TABLE categories ADD COLUMN remote_id TEXT", []);
+
+    let _ = conn.execute("ALTER TABLE channels ADD COLUMN sort_order INTEGER DEFAULT 0", []);
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS channels (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id   INTEGER NOT NULL,
+            name          TEXT NOT NULL,
+            logo_url      TEXT,
+            stream_url    TEXT NOT NULL,
+            category      TEXT,
+            category_id   INTEGER,
+            epg_channel_id TEXT,
+            is_favorite   BOOLEAN NOT NULL DEFAULT false,
+            is_hidden     BOOLEAN NOT NULL DEFAULT false,
+            sort_order    INTEGER NOT NULL DEFAULT 0,
+            catchup_days  INTEGER NOT NULL DEFAULT 0,
+            channel_number INTEGER,
+            remote_id     INTEGER,
+            epg_shift     INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+            FOREIGN KEY(category_id) REFERENCES categories(id) ON DELETE SET NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    let _ = conn.execute("ALTER TABLE channels ADD COLUMN catchup_days INTEGER NOT NULL DEFAULT 0", []);
+    let _ = conn.execute("ALTER TABLE channels ADD COLUMN channel_number INTEGER", []);
+    // Minutes the channel's guide is moved by (M3U tvg-shift, or set by the user)
+    let _ = conn.execute("ALTER TABLE channels ADD COLUMN epg_shift INTEGER NOT NULL DEFAULT 0", []);
+    // Channels used to be keyed on the provider's stream id alone. Older rows keep their
+    // id as the local key (so favorites, hidden flags and history stay attached) and
+    // take it over as their remote id.
+    let _ = conn.execute("ALTER TABLE channels ADD COLUMN remote_id INTEGER", []);
+    conn.execute("UPDATE channels SET remote_id = id WHERE remote_id IS NULL", []).map_err(|e| e.to_string())?;
+    // Channels saved before categories were linked only carry the category name
+    conn.execute(
+        "UPDATE channels SET category_id = (SELECT c.id FROM categories c WHERE c.playlist_id = channels.playlist_id AND c.name = channels.category LIMIT 1)
+         WHERE category_id IS NULL AND category IS NOT NULL",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vod_categories (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id   INTEGER NOT NULL,
+            remote_id     TEXT NOT NULL,
+            name          TEXT NOT NULL,
+            type          TEXT NOT NULL,
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS movies (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id     INTEGER NOT NULL,
+            stream_id       INTEGER NOT NULL,
+            name            TEXT NOT NULL,
+            stream_url      TEXT NOT NULL,
+            poster_url      TEXT,
+            description     TEXT,
+            rating          REAL,
+            release_year    INTEGER,
+            added_at        TEXT,
+            category        TEXT,
+            category_id     INTEGER,
+            sort_order      INTEGER NOT NULL DEFAULT 0,
+            UNIQUE(playlist_id, stream_id),
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+            FOREIGN KEY(category_id) REFERENCES vod_categories(id) ON DELETE SET NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS series (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            playlist_id     INTEGER NOT NULL,
+            series_id       INTEGER NOT NULL,
+            name            TEXT NOT NULL,
+            poster_url      TEXT,
+            description     TEXT,
+            genre           TEXT,
+            rating          REAL,
+            release_year    INTEGER,
+            added_at        TEXT,
+            last_modified   TEXT,
+            category        TEXT,
+            category_id     INTEGER,
+            sort_order      INTEGER NOT NULL DEFAULT 0,
+            last_seen       TEXT,
+            info_fetched_at TEXT,
+            UNIQUE(playlist_id, series_id),
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE,
+            FOREIGN KEY(category_id) REFERENCES vod_categories(id) ON DELETE SET NULL
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS series_seasons (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            series_id       INTEGER NOT NULL,
+            season_number   INTEGER NOT NULL,
+            name            TEXT,
+            cover_url       TEXT,
+            UNIQUE(series_id, season_number),
+            FOREIGN KEY(series_id) REFERENCES series(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS series_episodes (
+            id              INTEGER PRIMARY KEY AUTOINCREMENT,
+            series_id       INTEGER NOT NULL,
+            episode_id      INTEGER NOT NULL,
+            season_number   INTEGER NOT NULL,
+            episode_number  INTEGER NOT NULL,
+            title           TEXT NOT NULL,
+            description     TEXT,
+            image_url       TEXT,
+            stream_url      TEXT NOT NULL,
+            duration        INTEGER,
+            FOREIGN KEY(series_id) REFERENCES series(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // VOD flags are keyed by the provider's item id so they survive catalogue refreshes
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vod_favorites (
+            playlist_id   INTEGER NOT NULL,
+            vod_type      TEXT NOT NULL,
+            remote_id     INTEGER NOT NULL,
+            added_at      TEXT NOT NULL,
+            PRIMARY KEY(playlist_id, vod_type, remote_id),
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS vod_watchlist (
+            playlist_id   INTEGER NOT NULL,
+            vod_type      TEXT NOT NULL,
+            remote_id     INTEGER NOT NULL,
+            added_at      TEXT NOT NULL,
+            PRIMARY KEY(playlist_id, vod_type, remote_id),
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS epg_entries (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            channel_id    INTEGER NOT NULL,
+            title         TEXT NOT NULL,
+            description   TEXT,
+            start_time    TEXT NOT NULL, 
+            end_time      TEXT NOT NULL, 
+            sub_title     TEXT,
+            categories    TEXT,
+            icon          TEXT,
+            episode_num   TEXT,
+            episode_onscreen TEXT,
+            rating        TEXT,
+            star_rating   TEXT,
+            credits       TEXT,
+            date          TEXT,
+            is_new        BOOLEAN NOT NULL DEFAULT false,
+            is_premiere   BOOLEAN NOT NULL DEFAULT false,
+            is_previously_shown BOOLEAN NOT NULL DEFAULT false,
+            -- Guide the programme came from: 'source:<id>' or 'playlist:<id>'
+            source_key    TEXT NOT NULL,
+            FOREIGN KEY(channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    // Programme details; categories and credits are JSON arrays
+    for column in [
+        "sub_title TEXT", "categories TEXT", "icon TEXT", "episode_num TEXT", "episode_onscreen TEXT", "rating TEXT", "star_rating TEXT",
+        "credits TEXT", "date TEXT", "is_new BOOLEAN NOT NULL DEFAULT false", "is_premiere BOOLEAN NOT NULL DEFAULT false",
+        "is_previously_shown BOOLEAN NOT NULL DEFAULT false",
+    ] {
+        let _ = conn.execute(&format!("ALTER TABLE epg_entries ADD COLUMN {}", column), []);
+    }
+    // Programmes stored before guides were keyed are backfilled as legacy rows. They stay until
+    // a guide fills their channel or they fall out of the retention window.
+    let _ = conn.execute(&format!("ALTER TABLE epg_entries ADD COLUMN source_key TEXT NOT NULL DEFAULT '{}'", EPG_LEGACY_SOURCE_KEY), []);
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS epg_sources (
+            id            INTEGER PRIMARY KEY AUTOINCREMENT,
+            name          TEXT NOT NULL,
+            url           TEXT NOT NULL,
+            playlist_id   INTEGER,
+            priority      INTEGER NOT NULL DEFAULT 0,
+            is_active     BOOLEAN NOT NULL DEFAULT true,
+            status        TEXT NOT NULL DEFAULT 'inactive',
+            error_message TEXT,
+            last_updated  TEXT,
+            time_offset   INTEGER NOT NULL DEFAULT 0,
+            FOREIGN KEY(playlist_id) REFERENCES playlists(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+    let _ = conn.execute("ALTER TABLE epg_sources ADD COLUMN time_offset INTEGER NOT NULL DEFAULT 0", []);
+
+    conn.execute(
+        "CREATE TABLE IF NOT EXISTS recently_watched (
+            channel_id    INTEGER PRIMARY KEY,
+            last_watched  TEXT NOT NULL,
+            FOREIGN KEY(channel_id) REFERENCES channels(id) ON DELETE CASCADE
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    // --- NEW: INDICES FOR PERFORMANCE ---
+    // This drastically speeds up 'DELETE FROM channels' because SQLite 
+    // doesn't have to scan the whole epg table for every channel deletion.
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_epg_channel_id ON epg_entries(channel_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_epg_time ON epg_entries(start_time, end_time)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_epg_source_key ON epg_entries(source_key)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_epg_programme ON epg_entries(source_key, channel_id, start_time, title)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_playlist_id ON channels(playlist_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_category_id ON channels(category_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_number ON channels(channel_number)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_channels_stream_url ON channels(stream_url)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_channels_remote_id ON channels(playlist_id, remote_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_categories_playlist_id ON categories(playlist_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE UNIQUE INDEX IF NOT EXISTS idx_categories_remote_id ON categories(playlist_id, remote_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_vod_categories_playlist_id ON vod_categories(playlist_id, type)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_movies_category_id ON movies(category_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_series_category_id ON series(category_id)", []).map_err(|e| e.to_string())?;
+    conn.execute("CREATE INDEX IF NOT EXISTS idx_series_episodes_series_id ON series_episodes(series_id, season_number)", []).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+
+
+#[tauri::command]
+fn get_playlists(app: tauri::AppHandle) -> Result<Vec<Playlist>, String> {
+    let conn = get_db_connection(&app)?;
+    let mut stmt = conn.prepare("SELECT * FROM playlists").map_err(|e| e.to_string())?;
+    let playlists = stmt.query_map([], map_row_to_playlist)
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<Playlist>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(playlists)
+}
+
+#[tauri::command]
+fn schedule_notification(_payload: Value) -> Result<(), String> { Ok(()) }
+
+#[tauri::command]
+fn get_settings(app: tauri::AppHandle) -> Result<Option<AppSettings>, String> {
+    let conn = get_db_connection(&app)?;
+    read_settings(&conn)
+}
+
+fn read_settings(conn: &rusqlite::Connection) -> Result<Option<AppSettings>, String> {
+    let mut stmt = conn.prepare("SELECT value FROM settings WHERE key = 'app_settings'").map_err(|e| e.to_string())?;
+    match stmt.query_row([], |row| row.get::<_, String>(0)) {
+        Ok(json_value) => {
+            let settings: AppSettings = serde_json::from_str(&json_value).map_err(|e| e.to_string())?;
+            Ok(Some(settings))
+        },
+        Err(rusqlite::Error::QueryReturnedNoRows) => Ok(None),
+        Err(e) => Err(e.to_string()),
+    }
+}
+#[tauri::command]
 fn save_settings(settings: AppSettings, app: tauri::AppHandle) -> Result<(), String> {
     let conn = get_db_connection(&app)?;
-    let json_value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
-    conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('app_settings', ?1)", rusqlite::params![json_value]).map_err(|e| e.to_string())?;
-    Ok(())
+    let json_value = serde_json::to_string(&settings).map_err(|e| e.to_string())?;
+    conn.execute("INSERT OR REPLACE INTO settings (key, value) VALUES ('app_settings', ?1)", rusqlite::params![json_value]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Outcome of one playlist in a refresh_all_playlists run
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct PlaylistRefreshReport {
+    playlist_id: i64,
+    name: String,
+    // "success", "error", "cancelled" or "skipped" (already refreshing)
+    outcome: String,
+    error: Option<String>,
+    error_code: Option<String>,
+    duration_ms: u64,
+    channels: i64,
+    movies: i64,
+    series: i64,
+}
+
+// Payload of the `refresh-all-report` event and result of refresh_all_playlists
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RefreshAllReport {
+    playlists: Vec<PlaylistRefreshReport>,
+    succeeded: usize,
+    failed: usize,
+    duration_ms: u64,
+}
+
+// Host a playlist's refresh will talk to, for the per-host limit. Local files have none.
+fn refresh_host(playlist: &Playlist) -> Option<String> {
+    let url = playlist.server_urls().into_iter().next()?;
+    reqwest::Url::parse(url.trim()).ok()?.host_str().map(|h| h.to_lowercase())
+}
+
+// Spawn order of a run: playlists of different hosts take turns. The global slots are handed
+// out in this order, so they do not all go to playlists queueing for the same host.
+fn interleave_by_host(playlists: Vec<Playlist>) -> Vec<(usize, Playlist)> {
+    let total = playlists.len();
+    let mut queues: Vec<std::collections::VecDeque<(usize, Playlist)>> = Vec::new();
+    let mut host_queue: HashMap<String, usize> = HashMap::new();
+    for (index, playlist) in playlists.into_iter().enumerate() {
+        // Local playlists share no limit, so each one gets its own turn
+        let queue = match refresh_host(&playlist) {
+            Some(host) => *host_queue.entry(host).or_insert_with(|| { queues.push(Default::default()); queues.len() - 1 }),
+            None => { queues.push(Default::default()); queues.len() - 1 },
+        };
+        queues[queue].push_back((index, playlist));
+    }
+    let mut ordered = Vec::with_capacity(total);
+    while ordered.len() < total {
+        ordered.extend(queues.iter_mut().filter_map(|queue| queue.pop_front()));
+    }
+    ordered
+}
+
+// Waits for a slot of the playlist's host, then for a global one. Taking them in this order means
+// a playlist queueing behind another of its host never holds a global slot, so refreshes on
+// other hosts keep running meanwhile.
+async fn acquire_refresh_slots<'a>(slots: &'a tokio::sync::Semaphore, host_slot: Option<&'a tokio::sync::Semaphore>) -> Result<(tokio::sync::SemaphorePermit<'a>, Option<tokio::sync::SemaphorePermit<'a>>), String> {
+    let host_permit = match host_slot {
+        Some(slot) => Some(slot.acquire().await.map_err(|e| e.to_string())?),
+        None => None,
+    };
+    let permit = slots.acquire().await.map_err(|e| e.to_string())?;
+    Ok((permit, host_permit))
+}
+
+fn playlist_refresh_report(app: &tauri::AppHandle, playlist: &Playlist, result: Result<(), RefreshError>, started: std::time::Instant) -> PlaylistRefreshReport {
+    let (outcome, error) = match result {
+        Ok(()) => ("success", None),
+        Err(e) if e.is_cancelled() => ("cancelled", None),
+        Err(e) => ("error", Some(e)),
+    };
+    let count = |table: &str| -> i64 {
+        get_db_connection(app).ok()
+            .and_then(|conn| conn.query_row(&format!("SELECT COUNT(*) FROM {} WHERE playlist_id = ?1", table), [playlist.id], |row| row.get(0)).ok())
+            .unwrap_or(0)
+    };
+    PlaylistRefreshReport {
+        playlist_id: playlist.id,
+        name: playlist.name.clone(),
+        outcome: outcome.to_string(),
+        error_code: error.as_ref().map(|e| e.kind.code().to_string()),
+        error: error.map(String::from),
+        duration_ms: started.elapsed().as_millis() as u64,
+        channels: count("channels"),
+        movies: count("movies"),
+        series: count("series"),
+    }
+}
+
+// Refreshes every active playlist, at most `refresh_concurrency` at a time and at most
+// `refresh_per_host` per provider host, so accounts with a connection cap are not tripped
+#[tauri::command]
+async fn refresh_all_playlists(app: tauri::AppHandle) -> Result<RefreshAllReport, String> {
+    let run_started = std::time::Instant::now();
+    let (playlists, concurrency, per_host) = {
+        let conn = get_db_connection(&app)?;
+        let playlists = conn.prepare("SELECT * FROM playlists WHERE is_active = true ORDER BY id")
+            .map_err(|e| e.to_string())?
+            .query_map([], map_row_to_playlist).map_err(|e| e.to_string())?
+            .collect::<Result<Vec<Playlist>, _>>().map_err(|e| e.to_string())?;
+        let settings = read_settings(&conn).unwrap_or(None);
+        let concurrency = settings.as_ref().map_or(default_refresh_concurrency(), |s| s.refresh_concurrency).max(1);
+        let per_host = settings.as_ref().map_or(default_refresh_per_host(), |s| s.refresh_per_host).max(1);
+        (playlists, concurrency, per_host)
+    };
+
+    let total = playlists.len();
+    let slots = Arc::new(tokio::sync::Semaphore::new(concurrency));
+    let mut host_slots: HashMap<String, Arc<tokio::sync::Semaphore>> = HashMap::new();
+    let mut reports: Vec<(usize, PlaylistRefreshReport)> = Vec::with_capacity(total);
+    let mut running = tokio::task::JoinSet::new();
+
+    // Tasks are registered up front so `cancel_all_refreshes` reaches every playlist of the run
+    for (index, playlist) in interleave_by_host(playlists) {
+        let task = match RefreshTask::begin(&app, playlist.id) {
+            Ok(task) => task,
+            Err(e) => {
+                let mut report = playlist_refresh_report(&app, &playlist, Ok(()), std::time::Instant::now());
+                report.outcome = "skipped".to_string();
+                report.error = Some(e);
+                reports.push((index, report));
+                continue;
+            },
+        };
+        let host_slot = refresh_host(&playlist)
+            .map(|host| host_slots.entry(host).or_insert_with(|| Arc::new(tokio::sync::Semaphore::new(per_host))).clone());
+        let slots = slots.clone();
+        let app = app.clone();
+        running.spawn(async move {
+            let _permits = acquire_refresh_slots(&slots, host_slot.as_deref()).await;
+            let started = std::time::Instant::now();
+            // A refresh cancelled while still queued never starts
+            let result = match task.check_cancelled() {
+                Ok(()) => run_refresh(&task).await,
+                Err(e) => Err(e),
+            };
+            (index, playlist_refresh_report(&app, &playlist, result, started))
+        });
+    }
+
+    let mut completed = reports.len();
+    while let Some(joined) = running.join_next().await {
+        let (index, report) = joined.map_err(|e| e.to_string())?;
+        reports.push((index, report));
+        completed += 1;
+        let _ = app.emit("refresh-all-progress", (completed, total));
+    }
+
+    reports.sort_by_key(|(index, _)| *index);
+    let playlists: Vec<PlaylistRefreshReport> = reports.into_iter().map(|(_, report)| report).collect();
+    let report = RefreshAllReport {
+        succeeded: playlists.iter().filter(|r| r.outcome == "success").count(),
+        failed: playlists.iter().filter(|r| r.outcome == "error").count(),
+        duration_ms: run_started.elapsed().as_millis() as u64,
+        playlists,
+    };
+    let _ = app.emit("refresh-all-report", &report);
+    app.emit("refresh-complete", ()).map_err(|e| e.to_string())?;
+    Ok(report)
+}
+
+#[tauri::command]
+fn get_channels(options: FetchOptions, app: tauri::AppHandle) -> Result<PaginatedResponse<Channel>, String> {
+    let conn = get_db_connection(&app)?;
+    let page = options.page.max(1);
+    let page_size = options.page_size;
+    let offset = (page - 1) * page_size;
+    
+    // 1. Determine Filter Mode
+    let mut is_favorites = false;
+    let mut is_recently_watched = false;
+    if let Some(obj) = options.filter.as_object() {
+        if let Some(type_val) = obj.get("type") {
+            match type_val.as_str() {
+                Some("favorites") => is_favorites = true,
+                Some("recently-watched") => is_recently_watched = true,
+                _ => {}
+            }
+        }
+    }
+
+    let has_search = !options.search_term.is_empty();
+    
+    // 2. Prepare Base Filter Values (We extract these so we can rebuild params later)
+    let show_hidden = options.show_hidden.unwrap_or(false);
+    
+    let mut category_filter_id: Option<i64> = None;
+    
+    // Category Logic (Only if NOT searching and NOT special views)
+    if !has_search && !is_favorites && !is_recently_watched {
+         if let Some(cat_id) = options.filter.as_i64() {
+             category_filter_id = Some(cat_id);
+         } 
+         else if let Some(obj) = options.filter.as_object() {
+            if let Some(val) = obj.get("categoryId") {
+                if !val.is_null() {
+                    if let Some(cat_id) = val.as_i64() {
+                        category_filter_id = Some(cat_id);
+                    }
+                }
+            }
+        }
+    }
+
+    // 3. Build Base SQL String
+    let mut base_where = Vec::new();
+    if is_favorites { base_where.push("is_favorite = ?"); }
+    base_where.push("is_hidden = ?");
+    if category_filter_id.is_some() {
+        base_where.push("category_id = ?");
+    }
+    
+    let base_where_sql = if base_where.is_empty() { String::new() } else { format!("WHERE {}", base_where.join(" AND ")) };
+
+    // Helper to generate a fresh param vector matching base_where_sql
+    // We cannot clone Box<dyn ToSql>, so we must reconstruct it.
+    let get_base_params = || -> Vec<Box<dyn rusqlite::ToSql>> {
+        let mut p: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+        if is_favorites { p.push(Box::new(true)); }
+        p.push(Box::new(show_hidden));
+        if let Some(cid) = category_filter_id {
+            p.push(Box::new(cid));
+        }
+        p
+    };
+
+    // Variables for results
+    let final_ids_for_page: Vec<i64>;
+    let total_count: i64;
+
+    if has_search {
+        // --- SEARCH LOGIC (Deep Search) ---
+        let search_pattern = format!("%{}%", options.search_term);
+        let now = chrono::Utc::now().to_rfc3339();
+
+        // Query A: Channels matching Name (Applied on top of base filters)
+        let sql_a_where = if base_where_sql.is_empty() { "WHERE name LIKE ?".to_string() } else { format!("{} AND name LIKE ?", base_where_sql) };
+        let sql_a = format!("SELECT id FROM channels {}", sql_a_where);
+        
+        // Query B: EPG Matches (Current Program)
+        let sql_b = "SELECT DISTINCT channel_id FROM epg_entries WHERE title LIKE ? AND start_time <= ? AND end_time > ?";
+
+        let mut all_valid_ids: Vec<i64> = Vec::new();
+
+        // Run A (Name Match)
+        let mut params_a = get_base_params(); // Fresh Params
+        params_a.push(Box::new(search_pattern.clone())); // Add Search Param
+        
+        let mut stmt_a = conn.prepare(&sql_a).map_err(|e| e.to_string())?;
+        let ids_a = stmt_a.query_map(rusqlite::params_from_iter(params_a.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
+        for id in ids_a { all_valid_ids.push(id.unwrap_or(0)); }
+
+        // Run B (EPG Match)
+        let mut stmt_b = conn.prepare(sql_b).map_err(|e| e.to_string())?;
+        let ids_b = stmt_b.query_map(rusqlite::params![search_pattern, now, now], |row| row.get(0)).map_err(|e| e.to_string())?;
+        for id in ids_b { all_valid_ids.push(id.unwrap_or(0)); }
+
+        // Deduplicate
+        all_valid_ids.sort();
+        all_valid_ids.dedup();
+
+        // Verify base filters for EPG results
+        // (Query B results didn't check hidden/favorites yet, we must verify them)
+        if !all_valid_ids.is_empty() {
+            let id_list = all_valid_ids.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",");
+            
+            let verify_sql = format!("SELECT id FROM channels {} AND id IN ({})", 
+                if base_where_sql.is_empty() { "WHERE 1=1".to_string() } else { base_where_sql.clone() }, 
+                id_list
+            );
+            
+            let mut stmt_verify = conn.prepare(&verify_sql).map_err(|e| e.to_string())?;
+            let params_verify = get_base_params(); // Fresh Params for verification
+            
+            let verified_iter = stmt_verify.query_map(rusqlite::params_from_iter(params_verify.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
+            
+            all_valid_ids = verified_iter.filter_map(|r| r.ok()).collect();
+        }
+
+        total_count = all_valid_ids.len() as i64;
+
+        // Manual Pagination in Memory
+        let start = offset as usize;
+        if start >= all_valid_ids.len() {
+            final_ids_for_page = Vec::new();
+        } else {
+            let end = (start + page_size as usize).min(all_valid_ids.len());
+            final_ids_for_page = all_valid_ids[start..end].to_vec();
+        }
+
+    } else {
+        // --- BROWSE LOGIC (Standard) ---
+        let table = if is_recently_watched { "channels c JOIN recently_watched rw ON c.id = rw.channel_id" } else { "channels c" };
+        
+        let order_sql = if is_recently_watched { 
+            String::from("ORDER BY rw.last_watched DESC")
+        } else { 
+            let dir = if options.sort_order == "desc" { "DESC" } else { "ASC" };
+            match options.sort_by.as_str() {
+                "name" => format!("ORDER BY c.name {}", dir),
+                // Unnumbered channels go last whatever the direction
+                "number" => format!("ORDER BY c.channel_number IS NULL, c.channel_number {}, c.sort_order", dir),
+                _ => format!("ORDER BY c.sort_order {}", dir),
+            }
+        };
+
+        // 1. Count Total
+        let params_count = get_base_params(); // Fresh Params
+        let count_sql = format!("SELECT COUNT(*) FROM {} {}", table, base_where_sql);
+        total_count = conn.query_row(&count_sql, rusqlite::params_from_iter(params_count.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
+        
+        // 2. Fetch Paginated IDs
+        let mut params_fetch = get_base_params(); // Fresh Params
+        params_fetch.push(Box::new(page_size));
+        params_fetch.push(Box::new(offset));
+
+        let fetch_sql = format!("SELECT c.id FROM {} {} {} LIMIT ? OFFSET ?", table, base_where_sql, order_sql);
+        
+        let mut stmt = conn.prepare(&fetch_sql).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map(rusqlite::params_from_iter(params_fetch.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
+        
+        final_ids_for_page = rows.filter_map(|r| r.ok()).collect();
+    }
+
+    if final_ids_for_page.is_empty() {
+        return Ok(PaginatedResponse { items: vec![], has_more: false, total: total_count });
+    }
+
+    // --- HYDRATION STEP (Fetch Details for IDs) ---
+    let ids_params: Vec<rusqlite::types::Value> = final_ids_for_page.iter().map(|&id| id.into()).collect();
+    let placeholders = final_ids_for_page.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+
+    // 1. Fetch EPG
+    let mut epg_map: HashMap<i64, Vec<EpgEntry>> = HashMap::new();
+    let epg_sql = format!("SELECT channel_id, {} FROM epg_entries WHERE channel_id IN ({})", EPG_ENTRY_COLUMNS, placeholders);
+    let mut epg_stmt = conn.prepare(&epg_sql).map_err(|e| e.to_string())?;
+    let epg_rows = epg_stmt.query_map(rusqlite::params_from_iter(ids_params.clone()), |row| {
+        Ok((row.get::<_, i64>(0)?, map_row_to_epg_entry(row, 1)?))
+    }).map_err(|e| e.to_string())?;
+    for row in epg_rows {
+        let (cid, entry) = row.map_err(|e| e.to_string())?;
+        epg_map.entry(cid).or_default().push(entry);
+    }
+
+    // 2. Fetch Channels
+    let chan_sql = format!("SELECT id, playlist_id, name, logo_url, stream_url, category, category_id, is_favorite, is_hidden, catchup_days, channel_number, epg_shift FROM channels WHERE id IN ({})", placeholders);
+    let mut final_stmt = conn.prepare(&chan_sql).map_err(|e| e.to_string())?;
+    let mut channels: Vec<Channel> = final_stmt.query_map(rusqlite::params_from_iter(ids_params), |row| {
+        let channel_id: i64 = row.get(0)?;
+        Ok(Channel {
+            id: channel_id, playlist_id: row.get(1)?, name: row.get(2)?, logo_url: row.get(3)?, stream_url: row.get(4)?,
+            epg: epg_map.remove(&channel_id).unwrap_or_default(), category: row.get(5)?, category_id: row.get(6)?, is_favorite: row.get(7)?, is_hidden: row.get(8)?,
+            catchup_days: row.get(9)?, channel_number: row.get(10)?, epg_shift: row.get(11)?,
+        })
+    }).map_err(|e| e.to_string())?.collect::<Result<Vec<Channel>, _>>().map_err(|e| e.to_string())?;
+
+    // Restore Order
+    let mut pos_map = HashMap::new();
+    for (i, id) in final_ids_for_page.iter().enumerate() { pos_map.insert(*id, i); }
+    channels.sort_by_key(|c| pos_map.get(&c.id).unwrap_or(&0));
+
+    Ok(PaginatedResponse { items: channels, has_more: (page * page_size) < total_count, total: total_count })
+}
+
+// Reads the VOD category filter out of FetchOptions: `{ type: 'category', categoryName }`,
+// `{ categoryId }` or a bare id, mirroring the channel category semantics.
+fn vod_category_filter(filter: &Value) -> (Option<String>, Option<i64>) {
+    if let Some(cat_id) = filter.as_i64() { return (None, Some(cat_id)); }
+    let Some(obj) = filter.as_object() else { return (None, None) };
+    let name = obj.get("categoryName").and_then(|v| v.as_str()).map(|s| s.to_string());
+    let id = obj.get("categoryId").and_then(|v| v.as_i64());
+    (name, id)
+}
+
+fn map_row_to_vod(row: &Row, vod_type: &str) -> rusqlite::Result<VodItem> {
+    let genres = row.get::<_, Option<String>>(9)?
+        .map(|g| g.split([',', '/']).map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    Ok(VodItem {
+        id: row.get(0)?,
+        playlist_id: row.get(1)?,
+        title: row.get(2)?,
+        stream_url: row.get(3)?,
+        image_url: row.get::<_, Option<String>>(4)?.unwrap_or_default(),
+        r#type: vod_type.to_string(),
+        description: row.get(5)?,
+        rating: row.get(6)?,
+        release_year: row.get(7)?,
+        added_at: row.get(8)?,
+        genres,
+        duration: None,
+        is_favorite: row.get(10)?,
+        is_on_watchlist: row.get(11)?,
+    })
+}
+
+// Table name, remote id column and SELECT list (matching map_row_to_vod) for a VOD type
+fn vod_select_sql(vod_type: &str) -> (&'static str, &'static str, String) {
+    let (table, remote_col, stream_col, genre_col) = match vod_type {
+        "series" => ("series", "series_id", "''", "m.genre"),
+        _ => ("movies", "stream_id", "m.stream_url", "NULL"),
+    };
+    let select = format!(
+        "SELECT m.id, m.playlist_id, m.name, {stream_col}, m.poster_url, m.description, m.rating, m.release_year, m.added_at, {genre_col},
+            EXISTS(SELECT 1 FROM vod_favorites f WHERE f.playlist_id = m.playlist_id AND f.vod_type = '{vod_type}' AND f.remote_id = m.{remote_col}),
+            EXISTS(SELECT 1 FROM vod_watchlist w WHERE w.playlist_id = m.playlist_id AND w.vod_type = '{vod_type}' AND w.remote_id = m.{remote_col})
+         FROM {table} m"
+    );
+    (table, remote_col, select)
+}
+
+// Shared paging/search/sort/category logic behind get_movies and get_series
+fn query_vod_page(conn: &rusqlite::Connection, vod_type: &str, options: &FetchOptions) -> Result<PaginatedResponse<VodItem>, String> {
+    let (table, remote_col, select_sql) = vod_select_sql(vod_type);
+    let page = options.page.max(1);
+    let page_size = options.page_size;
+    let offset = (page - 1) * page_size;
+
+    let filter_type = options.filter.get("type").and_then(|t| t.as_str()).unwrap_or("all");
+    let mut where_parts: Vec<String> = Vec::new();
+    let mut params: Vec<rusqlite::types::Value> = Vec::new();
+
+    match filter_type {
+        "favorites" | "watchlist" => {
+            let flag_table = if filter_type == "favorites" { "vod_favorites" } else { "vod_watchlist" };
+            where_parts.push(format!(
+                "EXISTS(SELECT 1 FROM {} x WHERE x.playlist_id = m.playlist_id AND x.vod_type = ? AND x.remote_id = m.{})",
+                flag_table, remote_col
+            ));
+            params.push(vod_type.to_string().into());
+        },
+        _ => {}
+    }
+
+    // Category filter only applies when not searching, like get_channels
+    if !options.search_term.is_empty() {
+        where_parts.push("m.name LIKE ?".to_string());
+        params.push(format!("%{}%", options.search_term).into());
+    } else if filter_type == "all" || filter_type == "category" {
+        match vod_category_filter(&options.filter) {
+            (Some(name), _) => { where_parts.push("m.category = ?".to_string()); params.push(name.into()); },
+            (None, Some(cat_id)) => { where_parts.push("m.category_id = ?".to_string()); params.push(cat_id.into()); },
+            _ => {}
+        }
+    }
+    let where_sql = if where_parts.is_empty() { String::new() } else { format!("WHERE {}", where_parts.join(" AND ")) };
+
+    let col = match options.sort_by.as_str() {
+        "name" | "title" => "m.name COLLATE NOCASE",
+        "rating" => "m.rating",
+        "year" => "m.release_year",
+        "added" => "m.added_at",
+        _ => "m.sort_order",
+    };
+    let dir = if options.sort_order == "desc" { "DESC" } else { "ASC" };
+
+    let count_sql = format!("SELECT COUNT(*) FROM {} m {}", table, where_sql);
+    let total_count: i64 = conn.query_row(&count_sql, rusqlite::params_from_iter(params.clone()), |row| row.get(0)).map_err(|e| e.to_string())?;
+
+    params.push(page_size.into());
+    params.push(offset.into());
+    let fetch_sql = format!("{} {} ORDER BY {} {}, m.id LIMIT ? OFFSET ?", select_sql, where_sql, col, dir);
+    let mut stmt = conn.prepare(&fetch_sql).map_err(|e| e.to_string())?;
+    let items = stmt.query_map(rusqlite::params_from_iter(params), |row| map_row_to_vod(row, vod_type))
+        .map_err(|e| e.to_string())?
+        .collect::<Result<Vec<VodItem>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    Ok(PaginatedResponse { items, has_more: (page * page_size) < total_count, total: total_count })
+}
+
+fn fetch_single_vod(conn: &rusqlite::Connection, vod_type: &str, id: i64) -> Result<VodItem, String> {
+    let (_, _, select_sql) = vod_select_sql(vod_type);
+    conn.query_row(&format!("{} WHERE m.id = ?1", select_sql), [id], |row| map_row_to_vod(row, vod_type))
+        .map_err(|e| e.to_string())
+}
+
+// Flips a VOD flag row (favorites / watchlist) for the provider item behind a local movie/series id
+fn toggle_vod_flag(conn: &rusqlite::Connection, flag_table: &str, vod_type: &str, id: i64) -> Result<VodItem, String> {
+    let (table, remote_col, _) = vod_select_sql(vod_type);
+    let (playlist_id, remote_id) = conn.query_row(
+        &format!("SELECT playlist_id, {} FROM {} WHERE id = ?1", remote_col, table),
+        [id],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?))
+    ).map_err(|e| e.to_string())?;
+
+    let removed = conn.execute(
+        &format!("DELETE FROM {} WHERE playlist_id = ?1 AND vod_type = ?2 AND remote_id = ?3", flag_table),
+        rusqlite::params![playlist_id, vod_type, remote_id],
+    ).map_err(|e| e.to_string())?;
+    if removed == 0 {
+        conn.execute(
+            &format!("INSERT INTO {} (playlist_id, vod_type, remote_id, added_at) VALUES (?1, ?2, ?3, ?4)", flag_table),
+            rusqlite::params![playlist_id, vod_type, remote_id, chrono::Utc::now().to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+    }
+
+    fetch_single_vod(conn, vod_type, id)
+}
+
+#[tauri::command]
+fn get_movies(options: FetchOptions, app: tauri::AppHandle) -> Result<PaginatedResponse<VodItem>, String> {
+    let conn = get_db_connection(&app)?;
+    query_vod_page(&conn, "movie", &options)
+}
+
+#[tauri::command]
+fn get_series(options: FetchOptions, app: tauri::AppHandle) -> Result<PaginatedResponse<VodItem>, String> {
+    let conn = get_db_connection(&app)?;
+    query_vod_page(&conn, "series", &options)
+}
+
+// Downloads get_series_info for one series and replaces its cached seasons/episodes
+async fn cache_series_info(conn: &mut rusqlite::Connection, series_row_id: i64, playlist_id: i64, remote_series_id: i64) -> Result<(), String> {
+    let playlist = get_playlist_by_id(conn, playlist_id).ok_or_else(|| format!("Playlist {} not found", playlist_id))?;
+    let account = XtreamAccount::from_playlist(&playlist)?;
+    let client = build_http_client(&playlist.http_profile)?;
+
+    let info_url = format!("{}&series_id={}", account.action_url("get_series_info"), remote_series_id);
+    let info = send_with_retry(client.get(&info_url)).await?
+        .json::<XtreamSeriesInfo>().await.map_err(|e| ErrorKind::Parse.error(format!("series info JSON: {}", e)))?;
+
+    let episode_lists: Vec<Value> = match info.episodes {
+        Value::Object(map) => map.into_iter().map(|(_, v)| v).collect(),
+        Value::Array(list) => list,
+        _ => Vec::new(),
+    };
+    let episodes: Vec<XtreamEpisode> = episode_lists.into_iter()
+        .filter_map(|list| serde_json::from_value::<Vec<XtreamEpisode>>(list).ok())
+        .flatten()
+        .collect();
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM series_seasons WHERE series_id = ?1", [series_row_id]).map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM series_episodes WHERE series_id = ?1", [series_row_id]).map_err(|e| e.to_string())?;
+    {
+        let mut season_stmt = tx.prepare(
+            "INSERT OR IGNORE INTO series_seasons (series_id, season_number, name, cover_url) VALUES (?1, ?2, ?3, ?4)"
+        ).map_err(|e| e.to_string())?;
+        for season in &info.seasons {
+            let Some(number) = json_i64(&season.season_number) else { continue };
+            season_stmt.execute(rusqlite::params![series_row_id, number, season.name, season.cover.as_deref().filter(|c| !c.is_empty())]).map_err(|e| e.to_string())?;
+        }
+
+        let mut episode_stmt = tx.prepare(
+            "INSERT INTO series_episodes (series_id, episode_id, season_number, episode_number, title, description, image_url, stream_url, duration)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)"
+        ).map_err(|e| e.to_string())?;
+        for episode in &episodes {
+            let Some(episode_id) = json_i64(&episode.id) else { continue };
+            let season_number = json_i64(&episode.season).unwrap_or(1);
+            let info_str = |key: &str| episode.info.get(key).and_then(|v| v.as_str()).filter(|v| !v.is_empty()).map(|v| v.to_string());
+
+            // Seasons missing from the "seasons" array still need a row to hang episodes on
+            season_stmt.execute(rusqlite::params![series_row_id, season_number, Option::<String>::None, Option::<String>::None]).map_err(|e| e.to_string())?;
+            episode_stmt.execute(rusqlite::params![
+                series_row_id, episode_id, season_number, json_i64(&episode.episode_num).unwrap_or(0), episode.title,
+                info_str("plot"), info_str("movie_image"), account.episode_url(episode_id, episode.container_extension.as_deref()),
+                episode.info.get("duration_secs").and_then(json_i64)
+            ]).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.execute("UPDATE series SET info_fetched_at = ?1 WHERE id = ?2", rusqlite::params![chrono::Utc::now().to_rfc3339(), series_row_id]).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+#[tauri::command]
+async fn get_seasons_for_series(series_id: i64, app: tauri::AppHandle) -> Result<Vec<Season>, String> {
+    let mut conn = get_db_connection(&app)?;
+    let (playlist_id, remote_series_id, info_fetched_at) = conn.query_row(
+        "SELECT playlist_id, series_id, info_fetched_at FROM series WHERE id = ?1",
+        [series_id],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, Option<String>>(2)?))
+    ).map_err(|e| e.to_string())?;
+
+    if info_fetched_at.is_none() {
+        cache_series_info(&mut conn, series_id, playlist_id, remote_series_id).await?;
+    }
+
+    let mut episodes_by_season: HashMap<i64, Vec<Episode>> = HashMap::new();
+    {
+        let mut stmt = conn.prepare(
+            "SELECT id, series_id, season_number, episode_number, title, description, image_url, stream_url, duration
+             FROM series_episodes WHERE series_id = ?1 ORDER BY season_number, episode_number, id"
+        ).map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([series_id], |row| Ok(Episode {
+            id: row.get(0)?, series_id: row.get(1)?, season_number: row.get(2)?, episode_number: row.get(3)?, title: row.get(4)?,
+            description: row.get(5)?, image_url: row.get::<_, Option<String>>(6)?.unwrap_or_default(), stream_url: row.get(7)?, duration: row.get(8)?,
+        })).map_err(|e| e.to_string())?;
+        for episode in rows.flatten() {
+            episodes_by_season.entry(episode.season_number).or_default().push(episode);
+        }
+    }
+
+    let mut stmt = conn.prepare("SELECT id, season_number, name, cover_url FROM series_seasons WHERE series_id = ?1 ORDER BY season_number").map_err(|e| e.to_string())?;
+    let seasons = stmt.query_map([series_id], |row| {
+        let season_number: i64 = row.get(1)?;
+        Ok(Season {
+            id: row.get(0)?, series_id, season_number, name: row.get(2)?, image_url: row.get(3)?,
+            episodes: episodes_by_season.remove(&season_number).unwrap_or_default(),
+        })
+    }).map_err(|e| e.to_string())?.collect::<Result<Vec<Season>, _>>().map_err(|e| e.to_string())?;
+
+    Ok(seasons)
+}
+
+#[tauri::command]
+fn get_vod_categories(vod_type: String, app: tauri::AppHandle) -> Result<Vec<VodCategory>, String> {
+    let conn = get_db_connection(&app)?;
+    // Categories are merged by name across playlists, which is what the VOD sidebar filters on
+    let mut stmt = conn.prepare("SELECT name FROM vod_categories WHERE type = ?1 GROUP BY name ORDER BY MIN(id)").map_err(|e| e.to_string())?;
+    let categories = stmt.query_map([vod_type], |row| {
+        let name: String = row.get(0)?;
+        Ok(VodCategory { id: name.clone(), name })
+    }).map_err(|e| e.to_string())?.collect::<Result<Vec<VodCategory>, _>>().map_err(|e| e.to_string())?;
+    Ok(categories)
+}
+#[tauri::command]
+fn get_categories(app: tauri::AppHandle) -> Result<Vec<Category>, String> {
+    let conn = get_db_connection(&app)?;
+    let mut stmt = conn.prepare("SELECT * FROM categories").map_err(|e| e.to_string())?;
+    let categories = stmt.query_map([], map_row_to_category).map_err(|e| e.to_string())?.collect::<Result<Vec<Category>, _>>().map_err(|e| e.to_string())?;
+    Ok(categories)
+}
+#[tauri::command]
+fn add_playlist(mut playlist_data: NewPlaylistData, app: tauri::AppHandle) -> Result<Playlist, String> {
+    let conn = get_db_connection(&app)?;
+    normalize_playlist_source(&mut playlist_data.r#type, &mut playlist_data.url, &mut playlist_data.username, &mut playlist_data.password);
+    normalize_backup_urls(&playlist_data.r#type, &playlist_data.url, &mut playlist_data.backup_urls);
+    build_http_client(&playlist_data.http_profile)?;
+    conn.execute("INSERT INTO playlists (name, url, type, error_message, username, password, mac_address, max_connections, expiration_date, last_updated, backup_urls, http_profile, status, is_active) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, 'inactive', true)",
+        rusqlite::params![playlist_data.name, playlist_data.url, playlist_data.r#type, playlist_data.error_message, playlist_data.username, playlist_data.password, playlist_data.mac_address, playlist_data.max_connections, playlist_data.expiration_date, playlist_data.last_updated, join_backup_urls(&playlist_data.backup_urls), playlist_data.http_profile.to_column()?],
+    ).map_err(|e| e.to_string())?;
+    let new_id = conn.last_insert_rowid();
+    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").map_err(|e| e.to_string())?;
+    let new_playlist = stmt.query_row(rusqlite::params![new_id], map_row_to_playlist).map_err(|e| e.to_string())?;
+    Ok(new_playlist)
+}
+#[tauri::command]
+fn update_playlist(mut playlist: Playlist, app: tauri::AppHandle) -> Result<Playlist, String> {
+    let conn = get_db_connection(&app)?;
+    normalize_playlist_source(&mut playlist.r#type, &mut playlist.url, &mut playlist.username, &mut playlist.password);
+    normalize_backup_urls(&playlist.r#type, &playlist.url, &mut playlist.backup_urls);
+    build_http_client(&playlist.http_profile)?;
+    conn.execute("UPDATE playlists SET name = ?1, url = ?2, type = ?3, is_active = ?4, status = ?5, error_message = ?6, username = ?7, password = ?8, mac_address = ?9, max_connections = ?10, expiration_date = ?11, last_updated = ?12, backup_urls = ?13, http_profile = ?14 WHERE id = ?15",
+        rusqlite::params![playlist.name, playlist.url, playlist.r#type, playlist.is_active, playlist.status, playlist.error_message, playlist.username, playlist.password, playlist.mac_address, playlist.max_connections, playlist.expiration_date, playlist.last_updated, join_backup_urls(&playlist.backup_urls), playlist.http_profile.to_column()?, playlist.id],
+    ).map_err(|e| e.to_string())?;
+    Ok(playlist) 
+}
+#[tauri::command]
+fn delete_playlist(id: i64, app: tauri::AppHandle) -> Result<(), String> {
+    let conn = get_db_connection(&app)?;
+    conn.execute("DELETE FROM playlists WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+    Ok(())
+}
+#[tauri::command]
+fn update_playlist_active_status(id: i64, is_active: bool, app: tauri::AppHandle) -> Result<Playlist, String> {
+    let conn = get_db_connection(&app)?;
+    conn.execute("UPDATE playlists SET is_active = ?1 WHERE id = ?2", rusqlite::params![is_active, id]).map_err(|e| e.to_string())?;
+    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").map_err(|e| e.to_string())?;
+    let updated_playlist = stmt.query_row(rusqlite::params![id], map_row_to_playlist).map_err(|e| e.to_string())?;
+    Ok(updated_playlist)
+}
+
+// --- SERVER FAILOVER ---
+
+// Remembers how a server URL did: a working URL becomes the playlist's active one and loses its
+// recorded failure, a failing one gets its error stored. Failures of URLs no longer configured are pruned.
+fn record_server_result(conn: &rusqlite::Connection, playlist_id: i64, url: &str, error: Option<&str>) -> Result<(), String> {
+    let Some(playlist) = get_playlist_by_id(conn, playlist_id) else { return Ok(()) };
+    let configured = playlist.server_urls();
+    let mut failures: Vec<ServerUrlFailure> = playlist.url_failures.into_iter()
+        .filter(|f| f.url != url && configured.contains(&f.url))
+        .collect();
+    if let Some(error) = error {
+        failures.push(ServerUrlFailure { url: url.to_string(), error: error.to_string(), failed_at: chrono::Utc::now().to_rfc3339() });
+    }
+    let failures_json = if failures.is_empty() { None } else { Some(serde_json::to_string(&failures).map_err(|e| e.to_string())?) };
+
+    match error {
+        Some(_) => conn.execute("UPDATE playlists SET url_failures = ?1 WHERE id = ?2", rusqlite::params![failures_json, playlist_id]),
+        None => conn.execute("UPDATE playlists SET active_url = ?1, url_failures = ?2 WHERE id = ?3", rusqlite::params![url, failures_json, playlist_id]),
+    }.map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Runs `attempt` against each server URL of the playlist in turn until one succeeds, recording
+// every outcome. Returns the playlist pointed at the working URL alongside the attempt's result.
+// A cancelled refresh stops the loop instead of trying the next server.
+async fn with_server_failover<T, F, Fut>(app: &tauri::AppHandle, playlist: &Playlist, mut attempt: F) -> Result<(Playlist, T), RefreshError>
+where
+    F: FnMut(Playlist) -> Fut,
+    Fut: std::future::Future<Output = Result<T, RefreshError>>,
+{
+    let urls = playlist.server_urls();
+    let mut last_error = ErrorKind::Provider.error("no server URL configured");
+    for url in &urls {
+        let candidate = playlist.with_url(url);
+        match attempt(candidate.clone()).await {
+            Ok(value) => {
+                let conn = get_db_connection(app)?;
+                record_server_result(&conn, playlist.id, url, None)?;
+                return Ok((candidate, value));
+            },
+            Err(e) if e.is_cancelled() => return Err(e),
+            Err(e) => {
+                let conn = get_db_connection(app)?;
+                record_server_result(&conn, playlist.id, url, Some(&e.message))?;
+                last_error = e;
+            },
+        }
+    }
+    if urls.len() > 1 {
+        // The failure keeps the kind of the last server's error
+        Err(RefreshError { message: format!("All {} server URLs failed. Last error: {}", urls.len(), last_error), ..last_error })
+    } else {
+        Err(last_error)
+    }
+}
+
+// --- REFRESH PROGRESS & CANCELLATION ---
+
+const REFRESH_CANCELLED: &str = "Refresh cancelled";
+// Download progress is reported every PROGRESS_BYTE_STEP bytes, insert progress every PROGRESS_ROW_STEP rows
+const PROGRESS_BYTE_STEP: usize = 512 * 1024;
+const PROGRESS_ROW_STEP: usize = 500;
+
+// Payload of the `refresh-progress` event
+#[derive(serde::Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct RefreshProgress {
+    playlist_id: i64,
+    // "live", "movies" or "series"
+    catalogue: String,
+    // "account", "download", "parse", "insert", "done" or "cancelled"
+    phase: String,
+    processed: usize,
+    total: Option<usize>,
+}
+
+// Cancellation flags of the refreshes currently running, keyed by playlist id
+#[derive(Default)]
+struct RefreshRegistry {
+    running: Mutex<HashMap<i64, Arc<AtomicBool>>>,
+}
+
+// One running refresh: reports progress and carries the cancellation flag checked
+// between download chunks and insert batches. Dropping it unregisters the refresh.
+struct RefreshTask {
+    app: Option<tauri::AppHandle>,
+    playlist_id: i64,
+    cancelled: Arc<AtomicBool>,
+}
+
+impl RefreshTask {
+    fn begin(app: &tauri::AppHandle, playlist_id: i64) -> Result<Self, String> {
+        let registry = app.state::<RefreshRegistry>();
+        let mut running = registry.running.lock().map_err(|e| e.to_string())?;
+        if running.contains_key(&playlist_id) {
+            return Err(format!("Playlist {} is already refreshing", playlist_id));
+        }
+        let cancelled = Arc::new(AtomicBool::new(false));
+        running.insert(playlist_id, cancelled.clone());
+        Ok(RefreshTask { app: Some(app.clone()), playlist_id, cancelled })
+    }
+
+    // For dry runs such as connection tests: emits nothing and cannot be cancelled
+    fn untracked() -> Self {
+        RefreshTask { app: None, playlist_id: 0, cancelled: Arc::new(AtomicBool::new(false)) }
+    }
+
+    fn progress(&self, catalogue: &str, phase: &str, processed: usize, total: Option<usize>) {
+        if let Some(app) = &self.app {
+            let _ = app.emit("refresh-progress", RefreshProgress {
+                playlist_id: self.playlist_id, catalogue: catalogue.to_string(), phase: phase.to_string(), processed, total,
+            });
+        }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    fn check_cancelled(&self) -> Result<(), RefreshError> {
+        if self.is_cancelled() { Err(RefreshError::cancelled()) } else { Ok(()) }
+    }
+}
+
+impl Drop for RefreshTask {
+    fn drop(&mut self) {
+        if let Some(app) = &self.app {
+            if let Ok(mut running) = app.state::<RefreshRegistry>().running.lock() {
+                running.remove(&self.playlist_id);
+            }
+        }
+    }
+}
+
+// Reads a response body chunk by chunk, reporting download progress and honouring cancellation
+async fn read_body(mut resp: reqwest::Response, task: &RefreshTask, catalogue: &str) -> Result<Vec<u8>, RefreshError> {
+    let total = resp.content_length().map(|len| len as usize);
+    let mut body = Vec::with_capacity(total.unwrap_or(0));
+    let mut next_report = 0;
+    while let Some(chunk) = resp.chunk().await.map_err(request_error)? {
+        task.check_cancelled()?;
+        body.extend_from_slice(&chunk);
+        if body.len() >= next_report {
+            task.progress(catalogue, "download", body.len(), total);
+            next_report = body.len() + PROGRESS_BYTE_STEP;
+        }
+    }
+    task.progress(catalogue, "download", body.len(), total);
+    Ok(body)
+}
+
+#[tauri::command]
+fn cancel_refresh(playlist_id: i64, app: tauri::AppHandle) -> Result<bool, String> {
+    let registry = app.state::<RefreshRegistry>();
+    let running = registry.running.lock().map_err(|e| e.to_string())?;
+    match running.get(&playlist_id) {
+        Some(flag) => {
+            flag.store(true, Ordering::Relaxed);
+            Ok(true)
+        },
+        None => Ok(false),
+    }
+}
+
+#[tauri::command]
+fn cancel_all_refreshes(app: tauri::AppHandle) -> Result<usize, String> {
+    let registry = app.state::<RefreshRegistry>();
+    let running = registry.running.lock().map_err(|e| e.to_string())?;
+    for flag in running.values() { flag.store(true, Ordering::Relaxed); }
+    Ok(running.len())
+}
+
+// Rewrites the stored live URLs of an Xtream playlist for its current output format
+fn apply_output_format(conn: &mut rusqlite::Connection, playlist: &Playlist) -> Result<usize, String> {
+    if playlist.r#type != "xtream" { return Ok(0); }
+    // Keep the URLs on the server the last refresh used
+    let server = playlist.server_urls().into_iter().next().unwrap_or_default();
+    let account = XtreamAccount::from_playlist(&playlist.with_url(&server))?;
+    let output_format = playlist.live_output_format();
+
+    let ids: Vec<(i64, i64)> = {
+        let mut stmt = conn.prepare("SELECT id, remote_id FROM channels WHERE playlist_id = ?1").map_err(|e| e.to_string())?;
+        let rows = stmt.query_map([playlist.id], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| e.to_string())?;
+        rows.collect::<Result<Vec<(i64, i64)>, _>>().map_err(|e| e.to_string())?
+    };
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx.prepare("UPDATE channels SET stream_url = ?1 WHERE id = ?2").map_err(|e| e.to_string())?;
+        for (id, remote_id) in &ids {
+            stmt.execute(rusqlite::params![account.live_url(*remote_id, output_format), id]).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(ids.len())
+}
+
+#[tauri::command]
+fn set_playlist_output_format(id: i64, output_format: Option<String>, app: tauri::AppHandle) -> Result<Playlist, String> {
+    let mut conn = get_db_connection(&app)?;
+    let mut playlist = get_playlist_by_id(&conn, id).ok_or_else(|| format!("Playlist {} not found", id))?;
+
+    let output_format = output_format.map(|f| f.trim().trim_start_matches('.').to_lowercase()).filter(|f| !f.is_empty());
+    if let Some(format) = &output_format {
+        if format != "ts" && format != "m3u8" {
+            return Err(format!("Unsupported output format: {}", format));
+        }
+        if !playlist.allowed_output_formats.is_empty() && !playlist.allowed_output_formats.contains(format) {
+            return Err(format!("The server only allows: {}", playlist.allowed_output_formats.join(", ")));
+        }
+    }
+
+    conn.execute("UPDATE playlists SET output_format = ?1 WHERE id = ?2", rusqlite::params![output_format, id]).map_err(|e| e.to_string())?;
+    playlist.output_format = output_format;
+    apply_output_format(&mut conn, &playlist)?;
+    Ok(playlist)
+}
+
+const DEFAULT_USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36";
+
+// The read timeout applies between chunks, so large catalogues may take longer than it in total
+fn build_http_client(profile: &HttpProfile) -> Result<Client, String> {
+    let mut headers = reqwest::header::HeaderMap::new();
+    for header in &profile.headers {
+        let name = reqwest::header::HeaderName::from_bytes(header.name.trim().as_bytes())
+            .map_err(|_| format!("Invalid header name: {}", header.name))?;
+        let value = reqwest::header::HeaderValue::from_str(header.value.trim())
+            .map_err(|_| format!("Invalid value for header {}", header.name))?;
+        headers.append(name, value);
+    }
+
+    let mut builder = reqwest::Client::builder()
+        .user_agent(profile.custom_user_agent().unwrap_or(DEFAULT_USER_AGENT))
+        .default_headers(headers)
+        .connect_timeout(std::time::Duration::from_secs(profile.connect_timeout_secs.unwrap_or(15)))
+        .read_timeout(std::time::Duration::from_secs(profile.read_timeout_secs.unwrap_or(120)))
+        .danger_accept_invalid_certs(profile.accept_invalid_certs);
+    if let Some(proxy) = profile.proxy_url() {
+        builder = builder.proxy(reqwest::Proxy::all(proxy).map_err(|e| format!("Invalid proxy: {}", e))?);
+    }
+    builder.build().map_err(|e| e.to_string())
+}
+
+// --- RETRIES & ERROR CLASSIFICATION ---
+
+// Attempts per request, and the delay before the first retry (doubled for each further one)
+const HTTP_MAX_ATTEMPTS: u32 = 3;
+const HTTP_RETRY_BASE_DELAY_MS: u64 = 1000;
+
+// What went wrong in a refresh, stored as a code next to the message on the playlist
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum ErrorKind {
+    Auth,
+    Network,
+    Timeout,
+    Provider,
+    Parse,
+    // The database or another local fault, not the provider
+    Internal,
+    Cancelled,
+}
+
+impl ErrorKind {
+    fn code(self) -> &'static str {
+        match self {
+            ErrorKind::Auth => "auth",
+            ErrorKind::Network => "network",
+            ErrorKind::Timeout => "timeout",
+            ErrorKind::Provider => "provider",
+            ErrorKind::Parse => "parse",
+            ErrorKind::Internal => "internal",
+            ErrorKind::Cancelled => "cancelled",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ErrorKind::Auth => "Authentication failed",
+            ErrorKind::Network => "Network error",
+            ErrorKind::Timeout => "Request timed out",
+            ErrorKind::Provider => "Provider error",
+            ErrorKind::Parse => "Unreadable response",
+            ErrorKind::Internal => "Internal error",
+            ErrorKind::Cancelled => "Refresh cancelled",
+        }
+    }
+
+    fn error(self, detail: impl std::fmt::Display) -> RefreshError {
+        RefreshError { kind: self, message: format!("{}: {}", self.label(), detail) }
+    }
+}
+
+// A failed refresh. The kind is set where the error is created and travels with
+// the message, so rewording a message never changes how the failure is reported or retried.
+#[derive(Debug, Clone, PartialEq)]
+struct RefreshError {
+    kind: ErrorKind,
+    message: String,
+}
+
+impl RefreshError {
+    fn cancelled() -> Self {
+        RefreshError { kind: ErrorKind::Cancelled, message: REFRESH_CANCELLED.to_string() }
+    }
+
+    fn is_cancelled(&self) -> bool {
+        self.kind == ErrorKind::Cancelled
+    }
+}
+
+impl std::fmt::Display for RefreshError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+// Plain string errors come from the database and other local code; anything about the provider
+// or the user is created with its own kind
+impl From<String> for RefreshError {
+    fn from(message: String) -> Self {
+        RefreshError { kind: ErrorKind::Internal, message }
+    }
+}
+
+// Commands still answer the UI with plain strings
+impl From<RefreshError> for String {
+    fn from(e: RefreshError) -> Self {
+        e.message
+    }
+}
+
+fn request_error(e: reqwest::Error) -> RefreshError {
+    if e.is_timeout() {
+        ErrorKind::Timeout.error(e)
+    } else if e.is_decode() {
+        ErrorKind::Parse.error(e)
+    } else {
+        ErrorKind::Network.error(e)
+    }
+}
+
+fn status_error(status: reqwest::StatusCode) -> RefreshError {
+    if status == reqwest::StatusCode::UNAUTHORIZED || status == reqwest::StatusCode::FORBIDDEN {
+        ErrorKind::Auth.error(format!("HTTP {}", status))
+    } else {
+        ErrorKind::Provider.error(format!("HTTP {}", status))
+    }
+}
+
+// Sends a request, retrying with exponential backoff while the failure looks transient:
+// connection problems, timeouts, 5xx answers and rate limiting. Other answers are final.
+// Only getting the response is retried: a body that breaks off while it is being read fails
+// the fetch, and the refresh is left to the server failover or the next refresh.
+async fn send_with_retry(request: reqwest::RequestBuilder) -> Result<reqwest::Response, RefreshError> {
+    let mut attempt = 1;
+    loop {
+        let this_try = request.try_clone().ok_or_else(|| "Request cannot be retried".to_string())?;
+        let (error, transient) = match this_try.send().await {
+            Ok(resp) if resp.status().is_success() => return Ok(resp),
+            Ok(resp) => {
+                let status = resp.status();
+                (status_error(status), status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS)
+            },
+            Err(e) => {
+                let transient = e.is_timeout() || e.is_connect() || e.is_request();
+                (request_error(e), transient)
+            },
+        };
+        if !transient || attempt >= HTTP_MAX_ATTEMPTS {
+            return Err(error);
+        }
+        let delay = HTTP_RETRY_BASE_DELAY_MS << (attempt - 1);
+        tokio::time::sleep(std::time::Duration::from_millis(delay)).await;
+        attempt += 1;
+    }
+}
+
+// Channel row as currently stored, compared against the provider list on refresh
+struct StoredChannel {
+    id: i64,
+    name: String,
+    logo_url: Option<String>,
+    stream_url: String,
+    category_id: Option<i64>,
+    category: Option<String>,
+    epg_channel_id: Option<String>,
+    sort_order: i64,
+    catchup_days: i64,
+    channel_number: Option<i64>,
+    epg_shift: i64,
+}
+
+// Applies a provider channel list to the stored one inside a single transaction. Batches can
+// be pushed as they arrive: new channels are inserted and changed ones updated in place, then
+// `finish` deletes the channels the provider no longer lists and commits. Channels and
+// categories are matched on (playlist_id, remote_id), so survivors keep their local id and with
+// it favorite/hidden flags, watch history and EPG. Dropping it unfinished rolls everything back.
+struct LiveChannelSync<'a> {
+    conn: &'a rusqlite::Connection,
+    playlist_id: i64,
+    existing: HashMap<i64, StoredChannel>,
+    category_ids: HashMap<String, i64>,
+    seen: HashSet<i64>,
+    summary: ChannelChangeSummary,
+    finished: bool,
+}
+
+impl<'a> LiveChannelSync<'a> {
+    fn begin(conn: &'a rusqlite::Connection, playlist_id: i64, categories: &[LiveCategory]) -> Result<Self, String> {
+        // --- EXISTING CHANNELS (remote id -> stored row) ---
+        let mut existing: HashMap<i64, StoredChannel> = HashMap::new();
+        {
+            let mut stmt = conn.prepare(
+                "SELECT remote_id, id, name, logo_url, stream_url, category_id, category, epg_channel_id, sort_order, catchup_days, channel_number, epg_shift
+                 FROM channels WHERE playlist_id = ?1"
+            ).map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([playlist_id], |row| {
+                Ok((row.get::<_, i64>(0)?, StoredChannel {
+                    id: row.get(1)?, name: row.get(2)?, logo_url: row.get(3)?, stream_url: row.get(4)?,
+                    category_id: row.get(5)?, category: row.get(6)?, epg_channel_id: row.get(7)?,
+                    sort_order: row.get(8)?, catchup_days: row.get(9)?, channel_number: row.get(10)?, epg_shift: row.get(11)?,
+                }))
+            }).map_err(|e| e.to_string())?;
+            for row in rows {
+                let (remote_id, stored) = row.map_err(|e| e.to_string())?;
+                existing.insert(remote_id, stored);
+            }
+        }
+
+        conn.execute_batch("BEGIN").map_err(|e| e.to_string())?;
+        let mut sync = LiveChannelSync {
+            conn, playlist_id, existing,
+            category_ids: HashMap::new(),
+            seen: HashSet::new(),
+            summary: ChannelChangeSummary { playlist_id, ..Default::default() },
+            finished: false,
+        };
+        sync.upsert_categories(categories)?;
+        Ok(sync)
+    }
+
+    // Categories keep their local id and is_hidden flag; those no longer listed are removed
+    fn upsert_categories(&mut self, categories: &[LiveCategory]) -> Result<(), String> {
+        let mut by_remote: HashMap<String, i64> = HashMap::new();
+        // Rows saved before remote ids were stored can only be matched by name
+        let mut by_name: HashMap<String, i64> = HashMap::new();
+        {
+            let mut stmt = self.conn.prepare("SELECT id, remote_id, name FROM categories WHERE playlist_id = ?1").map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([self.playlist_id], |row| {
+                Ok((row.get::<_, i64>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, String>(2)?))
+            }).map_err(|e| e.to_string())?;
+            for row in rows {
+                match row.map_err(|e| e.to_string())? {
+                    (id, Some(remote_id), _) => { by_remote.insert(remote_id, id); },
+                    (id, None, name) => { by_name.entry(name).or_insert(id); },
+                }
+            }
+        }
+
+        let mut update_stmt = self.conn.prepare("UPDATE categories SET remote_id = ?1, name = ?2 WHERE id = ?3").map_err(|e| e.to_string())?;
+        let mut insert_stmt = self.conn.prepare("INSERT INTO categories (playlist_id, remote_id, name, is_hidden) VALUES (?1, ?2, ?3, false)").map_err(|e| e.to_string())?;
+        for cat in categories {
+            if self.category_ids.contains_key(&cat.remote_id) { continue; }
+            let id = match by_remote.remove(&cat.remote_id).or_else(|| by_name.remove(&cat.name)) {
+                Some(id) => {
+                    update_stmt.execute(rusqlite::params![cat.remote_id, cat.name, id]).map_err(|e| e.to_string())?;
+                    id
+                },
+                None => {
+                    insert_stmt.execute(rusqlite::params![self.playlist_id, cat.remote_id, cat.name]).map_err(|e| e.to_string())?;
+                    self.conn.last_insert_rowid()
+                },
+            };
+            self.category_ids.insert(cat.remote_id.clone(), id);
+        }
+
+        // Whatever is left is no longer listed by the provider
+        let mut del_stmt = self.conn.prepare("DELETE FROM categories WHERE id = ?1").map_err(|e| e.to_string())?;
+        for id in by_remote.values().chain(by_name.values()) {
+            del_stmt.execute([id]).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    fn push_batch(&mut self, entries: &[LiveChannelEntry]) -> Result<(), String> {
+        let mut insert_stmt = self.conn.prepare_cached(
+            "INSERT INTO channels (playlist_id, remote_id, name, logo_url, stream_url, category_id, category, epg_channel_id, sort_order, catchup_days, channel_number, epg_shift)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+        ).map_err(|e| e.to_string())?;
+        let mut update_stmt = self.conn.prepare_cached(
+            "UPDATE channels SET name = ?1, logo_url = ?2, stream_url = ?3, category_id = ?4, category = ?5, epg_channel_id = ?6,
+                sort_order = ?7, catchup_days = ?8, channel_number = ?9, epg_shift = ?10
+             WHERE id = ?11"
+        ).map_err(|e| e.to_string())?;
+
+        for entry in entries {
+            // Providers occasionally list a stream twice; the first occurrence wins
+            if !self.seen.insert(entry.remote_id) { continue; }
+            let category_id = entry.category_remote_id.as_deref().and_then(|r| self.category_ids.get(r)).copied();
+
+            let Some(stored) = self.existing.get(&entry.remote_id) else {
+                if let Err(e) = insert_stmt.execute(rusqlite::params![
+                    self.playlist_id, entry.remote_id, entry.name, entry.logo_url, entry.stream_url,
+                    category_id, entry.category, entry.epg_channel_id.as_deref(),
+                    entry.sort_order, entry.catchup_days, entry.channel_number, entry.epg_shift.unwrap_or(0)
+                ]) {
+                    println!("[ERROR] Failed to insert channel {}: {}", entry.name, e);
+                    self.seen.remove(&entry.remote_id);
+                    continue;
+                }
+                self.summary.added.push(entry.name.clone());
+                continue;
+            };
+
+            let changed = stored.name != entry.name
+                || stored.logo_url != entry.logo_url
+                || stored.stream_url != entry.stream_url
+                || stored.category_id != category_id
+                || stored.category.as_deref() != Some(entry.category.as_str())
+                || stored.epg_channel_id != entry.epg_channel_id
+                || stored.sort_order != entry.sort_order
+                || stored.catchup_days != entry.catchup_days
+                || stored.channel_number != entry.channel_number
+                || entry.epg_shift.is_some_and(|shift| shift != stored.epg_shift);
+            if !changed {
+                self.summary.unchanged += 1;
+                continue;
+            }
+
+            update_stmt.execute(rusqlite::params![
+                entry.name, entry.logo_url, entry.stream_url, category_id, entry.category, entry.epg_channel_id.as_deref(),
+                entry.sort_order, entry.catchup_days, entry.channel_number, entry.epg_shift.unwrap_or(stored.epg_shift), stored.id
+            ]).map_err(|e| e.to_string())?;
+            if stored.name != entry.name {
+                self.summary.renamed.push(RenamedChannel { id: stored.id, old_name: stored.name.clone(), new_name: entry.name.clone() });
+            }
+            self.summary.updated += 1;
+        }
+        Ok(())
+    }
+
+    fn finish(mut self) -> Result<ChannelChangeSummary, String> {
+        // --- REMOVE CHANNELS THE PROVIDER DROPPED ---
+        {
+            let mut del_epg = self.conn.prepare("DELETE FROM epg_entries WHERE channel_id = ?1").map_err(|e| e.to_string())?;
+            let mut del_hist = self.conn.prepare("DELETE FROM recently_watched WHERE channel_id = ?1").map_err(|e| e.to_string())?;
+            let mut del_chan = self.conn.prepare("DELETE FROM channels WHERE id = ?1").map_err(|e| e.to_string())?;
+            for (remote_id, stored) in &self.existing {
+                if self.seen.contains(remote_id) { continue; }
+                del_epg.execute([stored.id]).map_err(|e| e.to_string())?;
+                del_hist.execute([stored.id]).map_err(|e| e.to_string())?;
+                del_chan.execute([stored.id]).map_err(|e| e.to_string())?;
+                self.summary.removed.push(stored.name.clone());
+            }
+        }
+
+        self.conn.execute_batch("COMMIT").map_err(|e| e.to_string())?;
+        self.finished = true;
+        Ok(std::mem::take(&mut self.summary))
+    }
+}
+
+impl Drop for LiveChannelSync<'_> {
+    fn drop(&mut self) {
+        if !self.finished {
+            let _ = self.conn.execute_batch("ROLLBACK");
+        }
+    }
+}
+
+// Saves an already fetched channel list (M3U, Stalker) through LiveChannelSync
+fn save_live_channels(conn: &rusqlite::Connection, task: &RefreshTask, playlist_id: i64, categories: &[LiveCategory], entries: &[LiveChannelEntry]) -> Result<ChannelChangeSummary, RefreshError> {
+    let mut sync = LiveChannelSync::begin(conn, playlist_id, categories)?;
+    for (batch_index, batch) in entries.chunks(PROGRESS_ROW_STEP).enumerate() {
+        // Returning early drops the sync, which rolls every change back
+        task.check_cancelled()?;
+        task.progress("live", "insert", batch_index * PROGRESS_ROW_STEP, Some(entries.len()));
+        sync.push_batch(batch)?;
+    }
+    task.check_cancelled()?;
+    let summary = sync.finish()?;
+    task.progress("live", "insert", entries.len(), Some(entries.len()));
+    Ok(summary)
+}
+
+// --- LIVE STAGING ---
+// Xtream channel lists are staged in a TEMP table of the refresh's own connection while they
+// download. Writes there take no lock on the database file, so the LiveChannelSync write
+// transaction is only opened once the download is complete, and the list is never held in
+// memory as a whole.
+const LIVE_STAGING_COLUMNS: &str = "remote_id, name, logo_url, stream_url, category, category_remote_id, epg_channel_id, sort_order, channel_number, catchup_days, epg_shift";
+
+fn begin_live_staging(conn: &rusqlite::Connection) -> Result<(), String> {
+    conn.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS live_staging (
+            remote_id INTEGER NOT NULL, name TEXT NOT NULL, logo_url TEXT, stream_url TEXT NOT NULL, category TEXT NOT NULL,
+            category_remote_id TEXT, epg_channel_id TEXT, sort_order INTEGER NOT NULL, channel_number INTEGER,
+            catchup_days INTEGER NOT NULL, epg_shift INTEGER
+        );
+        DELETE FROM temp.live_staging;"
+    ).map_err(|e| e.to_string())
+}
+
+fn stage_live_entries(conn: &rusqlite::Connection, entries: &[LiveChannelEntry]) -> Result<(), String> {
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    {
+        let mut stmt = tx.prepare_cached(&format!(
+            "INSERT INTO temp.live_staging ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)", LIVE_STAGING_COLUMNS
+        )).map_err(|e| e.to_string())?;
+        for entry in entries {
+            stmt.execute(rusqlite::params![
+                entry.remote_id, entry.name, entry.logo_url, entry.stream_url, entry.category, entry.category_remote_id,
+                entry.epg_channel_id, entry.sort_order, entry.channel_number, entry.catchup_days, entry.epg_shift
+            ]).map_err(|e| e.to_string())?;
+        }
+    }
+    tx.commit().map_err(|e| e.to_string())
+}
+
+// Applies the staged channel list through LiveChannelSync, one page of rows at a time
+fn save_staged_live_channels(conn: &rusqlite::Connection, task: &RefreshTask, playlist_id: i64, categories: &[LiveCategory]) -> Result<ChannelChangeSummary, RefreshError> {
+    let total = conn.query_row("SELECT COUNT(*) FROM temp.live_staging", [], |row| row.get::<_, i64>(0)).map_err(|e| e.to_string())? as usize;
+    let mut sync = LiveChannelSync::begin(conn, playlist_id, categories)?;
+    let mut stmt = conn.prepare(&format!(
+        "SELECT rowid, {} FROM temp.live_staging WHERE rowid > ?1 ORDER BY rowid LIMIT ?2", LIVE_STAGING_COLUMNS
+    )).map_err(|e| e.to_string())?;
+
+    let mut last_rowid = 0;
+    let mut saved = 0;
+    loop {
+        // Returning early drops the sync, which rolls every change back
+        task.check_cancelled()?;
+        let rows = stmt.query_map(rusqlite::params![last_rowid, PROGRESS_ROW_STEP as i64], |row| {
+            Ok((row.get::<_, i64>(0)?, LiveChannelEntry {
+                remote_id: row.get(1)?, name: row.get(2)?, logo_url: row.get(3)?, stream_url: row.get(4)?, category: row.get(5)?,
+                category_remote_id: row.get(6)?, epg_channel_id: row.get(7)?, sort_order: row.get(8)?, channel_number: row.get(9)?,
+                catchup_days: row.get(10)?, epg_shift: row.get(11)?,
+            }))
+        }).map_err(|e| e.to_string())?;
+        let mut batch = Vec::with_capacity(PROGRESS_ROW_STEP);
+        for row in rows {
+            let (rowid, entry) = row.map_err(|e| e.to_string())?;
+            last_rowid = rowid;
+            batch.push(entry);
+        }
+        if batch.is_empty() { break; }
+        task.progress("live", "insert", saved, Some(total));
+        sync.push_batch(&batch)?;
+        saved += batch.len();
+    }
+    drop(stmt);
+
+    task.check_cancelled()?;
+    let summary = sync.finish()?;
+    conn.execute("DELETE FROM temp.live_staging", []).map_err(|e| e.to_string())?;
+    task.progress("live", "insert", total, Some(total));
+    Ok(summary)
+}
+
+async fn fetch_xtream_account_info(client: &Client, playlist: &Playlist) -> Result<XtreamAccountInfo, RefreshError> {
+    let account = XtreamAccount::from_playlist(playlist)?;
+    let resp = send_with_retry(client.get(account.account_url())).await?;
+    let body = resp.text().await.map_err(request_error)?;
+    // Some panels answer bad credentials with an empty array or an empty body instead of auth = 0
+    let body = body.trim();
+    if body.is_empty() || body == "[]" {
+        return Err(ErrorKind::Auth.error("invalid username or password"));
+    }
+    serde_json::from_str(body).map_err(|e| ErrorKind::Parse.error(format!("account info JSON: {}", e)))
+}
+
+fn save_account_info(conn: &rusqlite::Connection, playlist_id: i64, info: &XtreamAccountInfo) -> Result<(), String> {
+    conn.execute(
+        "UPDATE playlists SET expiration_date = ?1, max_connections = ?2, active_connections = ?3, account_status = ?4, allowed_output_formats = ?5, server_timezone = ?6 WHERE id = ?7",
+        rusqlite::params![
+            info.expiration_date(), json_i64(&info.user_info.max_connections), json_i64(&info.user_info.active_cons),
+            info.user_info.status, info.user_info.allowed_output_formats.join(","), info.server_info.timezone, playlist_id
+        ],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Splits a top-level JSON array that arrives in chunks into its raw elements, so a huge
+// provider response can be deserialized one item at a time instead of all at once
+#[derive(Default)]
+struct JsonArraySplitter {
+    element: Vec<u8>,
+    depth: usize,
+    in_element: bool,
+    in_string: bool,
+    escaped: bool,
+    started: bool,
+    finished: bool,
+}
+
+impl JsonArraySplitter {
+    fn feed(&mut self, chunk: &[u8], out: &mut Vec<Vec<u8>>) -> Result<(), String> {
+        for &b in chunk {
+            if self.finished { continue; }
+            if !self.started {
+                match b {
+                    b'[' => self.started = true,
+                    // Whitespace and a UTF-8 BOM may precede the array
+                    b' ' | b'\n' | b'\r' | b'\t' | 0xEF | 0xBB | 0xBF => {},
+                    _ => return Err("Expected a JSON array".to_string()),
+                }
+                continue;
+            }
+            if !self.in_element {
+                match b {
+                    b' ' | b'\n' | b'\r' | b'\t' | b',' => continue,
+                    b']' => { self.finished = true; continue; },
+                    _ => self.in_element = true,
+                }
+            }
+            if self.in_string {
+                self.element.push(b);
+                if self.escaped {
+                    self.escaped = false;
+                } else if b == b'\\' {
+                    self.escaped = true;
+                } else if b == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 { self.emit(out); }
+                }
+                continue;
+            }
+            match b {
+                b'"' => { self.in_string = true; self.element.push(b); },
+                b'{' | b'[' => { self.depth += 1; self.element.push(b); },
+                b'}' | b']' if self.depth > 0 => {
+                    self.depth -= 1;
+                    self.element.push(b);
+                    if self.depth == 0 { self.emit(out); }
+                },
+                // A scalar element ends at the next separator or at the end of the array
+                b',' if self.depth == 0 => self.emit(out),
+                b']' => { self.emit(out); self.finished = true; },
+                _ => self.element.push(b),
+            }
+        }
+        Ok(())
+    }
+
+    fn emit(&mut self, out: &mut Vec<Vec<u8>>) {
+        out.push(std::mem::take(&mut self.element));
+        self.in_element = false;
+    }
+
+    fn finish(&self) -> Result<(), String> {
+        if self.finished { Ok(()) } else { Err("Truncated JSON array".to_string()) }
+    }
+}
+
+// Turns a streamed `get_live_streams` body into channel entries as the chunks come in
+struct XtreamLiveDecoder {
+    splitter: JsonArraySplitter,
+    account: XtreamAccount,
+    output_format: Option<String>,
+    // Provider category id -> name
+    categories: HashMap<String, String>,
+    index: usize,
+}
+
+impl XtreamLiveDecoder {
+    fn new(account: XtreamAccount, output_format: Option<&str>, categories: &[XtreamCategory]) -> Self {
+        XtreamLiveDecoder {
+            splitter: JsonArraySplitter::default(),
+            account,
+            output_format: output_format.map(|f| f.to_string()),
+            categories: categories.iter().map(|c| (c.category_id.clone(), c.category_name.clone())).collect(),
+            index: 0,
+        }
+    }
+
+    fn feed(&mut self, chunk: &[u8]) -> Result<Vec<LiveChannelEntry>, RefreshError> {
+        let mut items = Vec::new();
+        self.splitter.feed(chunk, &mut items).map_err(|e| ErrorKind::Parse.error(format!("stream JSON: {}", e)))?;
+        let mut entries = Vec::with_capacity(items.len());
+        for item in items {
+            let stream: XtreamLiveStream = serde_json::from_slice(&item).map_err(|e| {
+                println!("[ERROR] Stream JSON Parse Failed: {}", e);
+                ErrorKind::Parse.error(format!("stream JSON: {}", e))
+            })?;
+            let index = self.index;
+            self.index += 1;
+            if let Some(entry) = self.entry(stream, index) { entries.push(entry); }
+        }
+        Ok(entries)
+    }
+
+    fn finish(&self) -> Result<(), RefreshError> {
+        self.splitter.finish().map_err(|e| ErrorKind::Parse.error(format!("stream JSON: {}", e)))
+    }
+
+    fn entry(&self, stream: XtreamLiveStream, index: usize) -> Option<LiveChannelEntry> {
+        let stream_id = json_i64(&stream.stream_id).filter(|id| *id != 0)?;
+        let category = stream.category_id.and_then(|id| self.categories.get_key_value(&id));
+        Some(LiveChannelEntry {
+            remote_id: stream_id,
+            stream_url: self.account.live_url(stream_id, self.output_format.as_deref()),
+            category: category.map_or("Uncategorized", |(_, name)| name.as_str()).to_string(),
+            category_remote_id: category.map(|(id, _)| id.clone()),
+            epg_channel_id: stream.epg_channel_id,
+            sort_order: index as i64,
+            channel_number: json_i64(&stream.num),
+            catchup_days: if json_i64(&stream.tv_archive) == Some(1) { json_i64(&stream.tv_archive_duration).unwrap_or(0) } else { 0 },
+            epg_shift: None,
+            name: stream.name,
+            logo_url: stream.stream_icon,
+        })
+    }
+}
+
+async fn fetch_xtream_live_categories(client: &Client, account: &XtreamAccount) -> Result<Vec<XtreamCategory>, RefreshError> {
+    println!("[DEBUG] Fetching Categories...");
+    send_with_retry(client.get(account.action_url("get_live_categories"))).await?
+        .json::<Vec<XtreamCategory>>().await.map_err(|e| ErrorKind::Parse.error(format!("live category JSON: {}", e)))
+}
+
+// Downloads `get_live_streams`, decoding the channels while the body is still arriving and
+// handing each decoded batch to `sink`. Returns the number of channels decoded.
+async fn stream_xtream_live<F>(client: &Client, url: &str, mut decoder: XtreamLiveDecoder, task: &RefreshTask, mut sink: F) -> Result<usize, RefreshError>
+where
+    F: FnMut(&[LiveChannelEntry]) -> Result<(), RefreshError>,
+{
+    let mut resp = send_with_retry(client.get(url)).await?;
+    let total_bytes = resp.content_length().map(|len| len as usize);
+    let mut downloaded = 0;
+    let mut next_report = 0;
+    let mut count = 0;
+
+    while let Some(chunk) = resp.chunk().await.map_err(request_error)? {
+        task.check_cancelled()?;
+        downloaded += chunk.len();
+        let entries = decoder.feed(&chunk)?;
+        count += entries.len();
+        sink(&entries)?;
+        if downloaded >= next_report {
+            task.progress("live", "download", downloaded, total_bytes);
+            next_report = downloaded + PROGRESS_BYTE_STEP;
+        }
+    }
+    decoder.finish()?;
+    task.check_cancelled()?;
+    task.progress("live", "download", downloaded, total_bytes);
+    Ok(count)
+}
+
+// Fetches the live catalogue of an Xtream playlist, passing the channels to `sink` batch by
+// batch as they are decoded. Returns the categories and the number of channels.
+async fn fetch_xtream_live<F>(client: &Client, playlist: &Playlist, task: &RefreshTask, sink: F) -> Result<(Vec<LiveCategory>, usize), RefreshError>
+where
+    F: FnMut(&[LiveChannelEntry]) -> Result<(), RefreshError>,
+{
+    let account = XtreamAccount::from_playlist(playlist)?;
+    let categories = fetch_xtream_live_categories(client, &account).await?;
+    let url = account.action_url("get_live_streams");
+    let live_categories: Vec<LiveCategory> = categories.iter()
+        .map(|c| LiveCategory { remote_id: c.category_id.clone(), name: c.category_name.clone() })
+        .collect();
+    let decoder = XtreamLiveDecoder::new(account, playlist.live_output_format(), &categories);
+    let count = stream_xtream_live(client, &url, decoder, task, sink).await?;
+    Ok((live_categories, count))
+}
+
+async fn fetch_xtream_movies(client: &Client, playlist: &Playlist, task: &RefreshTask) -> Result<(Vec<XtreamCategory>, Vec<XtreamVodStream>), RefreshError> {
+    let account = XtreamAccount::from_playlist(playlist)?;
+
+    let categories = send_with_retry(client.get(account.action_url("get_vod_categories"))).await?
+        .json::<Vec<XtreamCategory>>().await.map_err(|e| ErrorKind::Parse.error(format!("VOD category JSON: {}", e)))?;
+
+    let resp = send_with_retry(client.get(account.action_url("get_vod_streams"))).await?;
+    let body = read_body(resp, task, "movies").await?;
+    task.progress("movies", "parse", 0, None);
+    let movies = serde_json::from_slice::<Vec<XtreamVodStream>>(&body).map_err(|e| ErrorKind::Parse.error(format!("VOD JSON: {}", e)))?;
+
+    Ok((categories, movies))
+}
+
+// Replaces the movie catalogue (and its categories) of one playlist
+fn save_movies(conn: &mut rusqlite::Connection, task: &RefreshTask, playlist: &Playlist, categories: &[XtreamCategory], movies: &[XtreamVodStream]) -> Result<usize, RefreshError> {
+    let account = XtreamAccount::from_playlist(playlist)?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM vod_categories WHERE playlist_id = ?1 AND type = 'movie'", [playlist.id]).map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM movies WHERE playlist_id = ?1", [playlist.id]).map_err(|e| e.to_string())?;
+
+    let mut category_map: HashMap<&str, (i64, &str)> = HashMap::new();
+    {
+        let mut cat_stmt = tx.prepare("INSERT INTO vod_categories (playlist_id, remote_id, name, type) VALUES (?1, ?2, ?3, 'movie')").map_err(|e| e.to_string())?;
+        for cat in categories {
+            cat_stmt.execute(rusqlite::params![playlist.id, cat.category_id, cat.category_name]).map_err(|e| e.to_string())?;
+            category_map.insert(cat.category_id.as_str(), (tx.last_insert_rowid(), cat.category_name.as_str()));
+        }
+    }
+
+    let mut inserted_count = 0;
+    {
+        let mut movie_stmt = tx.prepare(
+            "INSERT OR REPLACE INTO movies (playlist_id, stream_id, name, stream_url, poster_url, description, rating, release_year, added_at, category, category_id, sort_order)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12)"
+        ).map_err(|e| e.to_string())?;
+
+        for (index, movie) in movies.iter().enumerate() {
+            if index % PROGRESS_ROW_STEP == 0 {
+                task.check_cancelled()?;
+                task.progress("movies", "insert", index, Some(movies.len()));
+            }
+            let stream_id = match json_i64(&movie.stream_id) { Some(id) if id != 0 => id, _ => continue };
+            let category = category_map.get(json_string(&movie.category_id).as_str()).copied();
+            let release_year = json_i64(&movie.year).or_else(|| extract_year(&movie.name));
+            let added_at = json_i64(&movie.added)
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|dt| dt.to_rfc3339());
+
+            if let Err(e) = movie_stmt.execute(rusqlite::params![
+                playlist.id, stream_id, movie.name, account.movie_url(stream_id, movie.container_extension.as_deref()),
+                movie.stream_icon, movie.plot, json_f64(&movie.rating), release_year, added_at,
+                category.map_or("Uncategorized", |c| c.1), category.map(|c| c.0), index as i64
+            ]) {
+                println!("[ERROR] Failed to insert movie {}: {}", movie.name, e);
+                continue;
+            }
+            inserted_count += 1;
+        }
+    }
+
+    task.check_cancelled()?;
+    tx.commit().map_err(|e| e.to_string())?;
+    task.progress("movies", "insert", movies.len(), Some(movies.len()));
+    Ok(inserted_count)
+}
+
+async fn fetch_xtream_series(client: &Client, playlist: &Playlist, task: &RefreshTask) -> Result<(Vec<XtreamCategory>, Vec<XtreamSeries>), RefreshError> {
+    let account = XtreamAccount::from_playlist(playlist)?;
+
+    let categories = send_with_retry(client.get(account.action_url("get_series_categories"))).await?
+        .json::<Vec<XtreamCategory>>().await.map_err(|e| ErrorKind::Parse.error(format!("series category JSON: {}", e)))?;
+
+    let resp = send_with_retry(client.get(account.action_url("get_series"))).await?;
+    let body = read_body(resp, task, "series").await?;
+    task.progress("series", "parse", 0, None);
+    let series = serde_json::from_slice::<Vec<XtreamSeries>>(&body).map_err(|e| ErrorKind::Parse.error(format!("series JSON: {}", e)))?;
+
+    Ok((categories, series))
+}
+
+// Upserts the series catalogue of one playlist. Local ids are kept stable so the
+// lazily cached seasons/episodes survive; the cache is dropped when `last_modified` moves.
+fn save_series(conn: &mut rusqlite::Connection, task: &RefreshTask, playlist_id: i64, categories: &[XtreamCategory], series: &[XtreamSeries]) -> Result<usize, RefreshError> {
+    let refresh_stamp = chrono::Utc::now().to_rfc3339();
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM vod_categories WHERE playlist_id = ?1 AND type = 'series'", [playlist_id]).map_err(|e| e.to_string())?;
+
+    let mut category_map: HashMap<&str, (i64, &str)> = HashMap::new();
+    {
+        let mut cat_stmt = tx.prepare("INSERT INTO vod_categories (playlist_id, remote_id, name, type) VALUES (?1, ?2, ?3, 'series')").map_err(|e| e.to_string())?;
+        for cat in categories {
+            cat_stmt.execute(rusqlite::params![playlist_id, cat.category_id, cat.category_name]).map_err(|e| e.to_string())?;
+            category_map.insert(cat.category_id.as_str(), (tx.last_insert_rowid(), cat.category_name.as_str()));
+        }
+    }
+
+    let mut saved_count = 0;
+    {
+        let mut series_stmt = tx.prepare(
+            "INSERT INTO series (playlist_id, series_id, name, poster_url, description, genre, rating, release_year, added_at, last_modified, category, category_id, sort_order, last_seen)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14)
+             ON CONFLICT(playlist_id, series_id) DO UPDATE SET
+                name = excluded.name, poster_url = excluded.poster_url, description = excluded.description, genre = excluded.genre,
+                rating = excluded.rating, release_year = excluded.release_year, added_at = excluded.added_at,
+                category = excluded.category, category_id = excluded.category_id, sort_order = excluded.sort_order, last_seen = excluded.last_seen,
+                info_fetched_at = CASE WHEN series.last_modified IS excluded.last_modified THEN series.info_fetched_at ELSE NULL END,
+                last_modified = excluded.last_modified"
+        ).map_err(|e| e.to_string())?;
+
+        for (index, show) in series.iter().enumerate() {
+            if index % PROGRESS_ROW_STEP == 0 {
+                task.check_cancelled()?;
+                task.progress("series", "insert", index, Some(series.len()));
+            }
+            let series_id = match json_i64(&show.series_id) { Some(id) if id != 0 => id, _ => continue };
+            let category = category_map.get(json_string(&show.category_id).as_str()).copied();
+            let release_year = show.release_date.as_deref().and_then(extract_year).or_else(|| extract_year(&show.name));
+            let last_modified = json_i64(&show.last_modified)
+                .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+                .map(|dt| dt.to_rfc3339());
+
+            if let Err(e) = series_stmt.execute(rusqlite::params![
+                playlist_id, series_id, show.name, show.cover, show.plot, show.genre, json_f64(&show.rating), release_year,
+                last_modified, last_modified, category.map_or("Uncategorized", |c| c.1), category.map(|c| c.0), index as i64, refresh_stamp
+            ]) {
+                println!("[ERROR] Failed to save series {}: {}", show.name, e);
+                continue;
+            }
+            saved_count += 1;
+        }
+    }
+
+    // Drop series the provider no longer lists, together with their cached episodes
+    tx.execute("DELETE FROM series WHERE playlist_id = ?1 AND last_seen IS NOT ?2", rusqlite::params![playlist_id, refresh_stamp]).map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM series_seasons WHERE series_id NOT IN (SELECT id FROM series)", []).map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM series_episodes WHERE series_id NOT IN (SELECT id FROM series)", []).map_err(|e| e.to_string())?;
+
+    task.check_cancelled()?;
+    tx.commit().map_err(|e| e.to_string())?;
+    task.progress("series", "insert", series.len(), Some(series.len()));
+    Ok(saved_count)
+}
+
+#[tauri::command]
+async fn refresh_playlist(playlist_id: i64, app: tauri::AppHandle) -> Result<(), String> {
+    let task = RefreshTask::begin(&app, playlist_id)?;
+    Ok(run_refresh(&task).await?)
+}
+
+// Refreshes one playlist under an already registered task. A refresh cancelled before its live
+// channels were saved rolls back the catalogue being written and puts the playlist back into
+// the status it had before. Once they are committed a cancel can no longer undo them, so the
+// playlist stays active with a note that movies and series were not updated. Any other
+// failure, wherever it happened, leaves the playlist in the error state.
+async fn run_refresh(task: &RefreshTask) -> Result<(), RefreshError> {
+    let app = task.app.clone().ok_or_else(|| "Refresh task has no app handle".to_string())?;
+    let playlist_id = task.playlist_id;
+    let previous_status = {
+        let conn = get_db_connection(&app)?;
+        get_playlist_by_id(&conn, playlist_id).map(|p| p.status).filter(|s| s != "loading").unwrap_or_else(|| "inactive".to_string())
+    };
+
+    let mut live_saved = false;
+    match refresh_catalogues(task, &app, &mut live_saved).await {
+        Ok(()) => Ok(()),
+        Err(e) if e.is_cancelled() => {
+            let conn = get_db_connection(&app)?;
+            if live_saved {
+                let note = ErrorKind::Cancelled.error("live channels were updated, movies and series were not");
+                update_playlist_status(&conn, playlist_id, "active", Some(&note))?;
+            } else {
+                update_playlist_status(&conn, playlist_id, &previous_status, None)?;
+            }
+            let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default());
+            task.progress("live", "cancelled", 0, None);
+            Err(e)
+        },
+        Err(e) => {
+            let conn = get_db_connection(&app)?;
+            Err(fail_refresh(&conn, &app, playlist_id, e))
+        },
+    }
+}
+
+// Sets `live_saved` once the live channels are committed, which a later failure does not undo
+async fn refresh_catalogues(task: &RefreshTask, app: &tauri::AppHandle, live_saved: &mut bool) -> Result<(), RefreshError> {
+    let start_time = std::time::Instant::now();
+    let playlist_id = task.playlist_id;
+    println!("[DEBUG] Starting refresh for playlist ID: {}", playlist_id);
+
+    let mut conn = get_db_connection(app)?;
+    let mut playlist = get_playlist_by_id(&conn, playlist_id).ok_or_else(|| format!("Playlist {} not found", playlist_id))?;
+
+    if playlist.r#type == "xtream" && (playlist.username.is_none() || playlist.password.is_none()) {
+        return Err(ErrorKind::Auth.error("missing username or password"));
+    }
+    
+    update_playlist_status(&conn, playlist_id, "loading", None)?;
+    let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default()); 
+
+    let client = build_http_client(&playlist.http_profile)?;
+
+    if playlist.r#type == "xtream" {
+        task.progress("live", "account", 0, None);
+        // The first server answering player_api.php serves the whole refresh, so stream URLs point at it
+        let client_ref = &client;
+        let (working, info) = with_server_failover(app, &playlist, |candidate| async move { fetch_xtream_account_info(client_ref, &candidate).await }).await?;
+        playlist = working;
+        save_account_info(&conn, playlist_id, &info)?;
+        if let Some(e) = info.status_error() {
+            return Err(e);
+        }
+        // The output format preference is checked against the freshly reported formats
+        playlist.allowed_output_formats = info.user_info.allowed_output_formats.clone();
+    }
+    task.check_cancelled()?;
+
+    let changes = match playlist.r#type.as_str() {
+        "m3u" | "stalker" => {
+            let client_ref = &client;
+            let (_, (categories, entries)) = with_server_failover(app, &playlist, |candidate| async move {
+                if candidate.r#type == "m3u" {
+                    fetch_m3u_live(client_ref, &candidate, task).await
+                } else {
+                    fetch_stalker_live(client_ref, &candidate, task).await
+                }
+            }).await?;
+            save_live_channels(&conn, task, playlist_id, &categories, &entries)?
+        },
+        _ => {
+            // Channels are staged while they download; the write transaction is only opened
+            // once the whole catalogue is in, so the database is not locked meanwhile
+            begin_live_staging(&conn)?;
+            let staging = &mut conn;
+            let (categories, _) = fetch_xtream_live(&client, &playlist, task, move |entries| {
+                stage_live_entries(staging, entries).map_err(RefreshError::from)
+            }).await?;
+            save_staged_live_channels(&conn, task, playlist_id, &categories)?
+        },
+    };
+    *live_saved = true;
+    let _ = app.emit("playlist-changes", &changes);
+    let channel_count = changes.added.len() + changes.updated + changes.unchanged;
+
+    // VOD is optional on many Xtream panels, so a failure here must not fail the live refresh
+    if playlist.r#type == "xtream" {
+        match fetch_xtream_movies(&client, &playlist, task).await {
+            Ok((vod_categories, movies)) => { save_movies(&mut conn, task, &playlist, &vod_categories, &movies)?; },
+            Err(e) => println!("[VOD] Skipping movies: {}", e),
+        }
+        task.check_cancelled()?;
+        match fetch_xtream_series(&client, &playlist, task).await {
+            Ok((series_categories, series)) => { save_series(&mut conn, task, playlist_id, &series_categories, &series)?; },
+            Err(e) => println!("[VOD] Skipping series: {}", e),
+        }
+        task.check_cancelled()?;
+    }
+
+    update_playlist_status(&conn, playlist_id, "active", None)?;
+    let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default());
+    task.progress("live", "done", channel_count, Some(channel_count));
+    println!("[DEBUG] SUCCESS! Playlist refresh complete. Total Time: {:.2?}", start_time.elapsed());
+    Ok(())
+}
+
+// Streams the live catalogue like a refresh would, but only counts it while decoding
+async fn count_xtream_live(client: &Client, playlist: &Playlist, task: &RefreshTask) -> Result<(usize, usize), RefreshError> {
+    let (categories, count) = fetch_xtream_live(client, playlist, task, |_| Ok(())).await?;
+    Ok((categories.len(), count))
+}
+
+// Any HTTP answer from the source's origin (even 404/405) proves the host is reachable
+async fn probe_source(client: &Client, source: &str) -> Result<(), String> {
+    if !(source.starts_with("http://") || source.starts_with("https://")) {
+        let path = source.strip_prefix("file://").unwrap_or(source);
+        return tokio::fs::metadata(path).await.map(|_| ()).map_err(|e| format!("Cannot open {}: {}", path, e));
+    }
+    let origin = reqwest::Url::parse(source).map_err(|e| format!("Invalid URL: {}", e))?.origin().ascii_serialization();
+    client.head(&origin).timeout(std::time::Duration::from_secs(15)).send().await.map(|_| ()).map_err(|e| e.to_string())
+}
+
+// Dry run of a refresh: authenticates and counts the catalogue without writing to the database
+#[tauri::command]
+async fn test_playlist_connection(mut playlist_data: NewPlaylistData) -> Result<ConnectionTestReport, String> {
+    normalize_playlist_source(&mut playlist_data.r#type, &mut playlist_data.url, &mut playlist_data.username, &mut playlist_data.password);
+    let playlist = Playlist {
+        name: playlist_data.name,
+        url: playlist_data.url,
+        r#type: playlist_data.r#type,
+        username: playlist_data.username,
+        password: playlist_data.password,
+        mac_address: playlist_data.mac_address,
+        http_profile: playlist_data.http_profile,
+        ..Default::default()
+    };
+    let task = RefreshTask::untracked();
+    let mut report = ConnectionTestReport::default();
+    let client = match build_http_client(&playlist.http_profile) {
+        Ok(client) => client,
+        Err(e) => { report.error = Some(e); return Ok(report); }
+    };
+
+    if let Err(e) = probe_source(&client, playlist.url.trim()).await {
+        report.error = Some(e);
+        return Ok(report);
+    }
+    report.reachable = true;
+
+    match playlist.r#type.as_str() {
+        "xtream" => {
+            let info = match fetch_xtream_account_info(&client, &playlist).await {
+                Ok(info) => info,
+                Err(e) => { report.error = Some(e.into()); return Ok(report); }
+            };
+            report.account_status = info.user_info.status.clone();
+            report.expiration_date = info.expiration_date();
+            report.max_connections = json_i64(&info.user_info.max_connections);
+            report.active_connections = json_i64(&info.user_info.active_cons);
+            if let Some(e) = info.status_error() {
+                report.error = Some(e.into());
+                return Ok(report);
+            }
+            report.authenticated = true;
+
+            match count_xtream_live(&client, &playlist, &task).await {
+                Ok((categories, channels)) => { report.category_count = categories; report.channel_count = channels; },
+                Err(e) => report.error = Some(e.into()),
+            }
+            report.movie_count = fetch_xtream_movies(&client, &playlist, &task).await.ok().map(|(_, movies)| movies.len());
+            report.series_count = fetch_xtream_series(&client, &playlist, &task).await.ok().map(|(_, series)| series.len());
+        },
+        "m3u" | "stalker" => {
+            let fetched = if playlist.r#type == "m3u" { fetch_m3u_live(&client, &playlist, &task).await } else { fetch_stalker_live(&client, &playlist, &task).await };
+            match fetched {
+                Ok((categories, entries)) => {
+                    report.authenticated = true;
+                    report.category_count = categories.len();
+                    report.channel_count = entries.len();
+                },
+                Err(e) => report.error = Some(e.into()),
+            }
+        },
+        other => report.error = Some(format!("Unsupported playlist type: {}", other)),
+    }
+
+    Ok(report)
+}
+
+#[tauri::command]
+fn toggle_category_visibility(id: i64, app: tauri::AppHandle) -> Result<Category, String> {
+    let conn = get_db_connection(&app)?;
+    let is_hidden: bool = conn.query_row("SELECT is_hidden FROM categories WHERE id = ?1", [id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    conn.execute("UPDATE categories SET is_hidden = ?1 WHERE id = ?2", rusqlite::params![!is_hidden, id]).map_err(|e| e.to_string())?;
+    let updated_category = conn.query_row("SELECT * FROM categories WHERE id = ?1", rusqlite::params![id], map_row_to_category).map_err(|e| e.to_string())?;
+    Ok(updated_category)
 }
 
 #[tauri::command]
-async fn refresh_all_playlists(app: tauri::AppHandle) -> Result<(), String> {
+fn batch_update_category_visibility(ids: Vec<i64>, is_hidden: bool, app: tauri::AppHandle) -> Result<Vec<Category>, String> {
     let conn = get_db_connection(&app)?;
-    let playlist_ids: Vec<i64> = conn.prepare("SELECT id FROM playlists WHERE is_active = true")
-        .map_err(|e| e.to_string())?
-        .query_map([], |row| row.get(0)).map_err(|e| e.to_string())?
-        .collect::<Result<Vec<i64>, _>>().map_err(|e| e.to_string())?;
-    let mut handles = vec![];
-    for id in playlist_ids {
-        let app_clone = app.clone();
-        handles.push(tokio::spawn(async move {
-            let _ = refresh_playlist(id, app_clone).await;
-        }));
-    }
-    for handle in handles { let _ = handle.await; }
-    app.emit("refresh-complete", ()).map_err(|e| e.to_string())?;
-    Ok(())
+    if ids.is_empty() { return Ok(vec![]); }
+    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let sql = format!("UPDATE categories SET is_hidden = ?1 WHERE id IN ({})", placeholders);
+    let mut params: Vec<rusqlite::types::Value> = vec![is_hidden.into()];
+    params.extend(ids.iter().map(|&id| id.into()));
+    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
+    let fetch_sql = format!("SELECT * FROM categories WHERE id IN ({})", placeholders);
+    let select_params: Vec<rusqlite::types::Value> = ids.iter().map(|&id| id.into()).collect();
+    let mut stmt = conn.prepare(&fetch_sql).map_err(|e| e.to_string())?;
+    let categories = stmt.query_map(rusqlite::params_from_iter(select_params), map_row_to_category).map_err(|e| e.to_string())?.collect::<Result<Vec<Category>, _>>().map_err(|e| e.to_string())?;
+    Ok(categories)
+}
+
+fn fetch_single_channel(conn: &rusqlite::Connection, id: i64) -> Result<Channel, String> {
+    let mut stmt = conn.prepare("SELECT id, playlist_id, name, logo_url, stream_url, category, category_id, is_favorite, is_hidden, catchup_days, channel_number, epg_shift FROM channels WHERE id = ?1").map_err(|e| e.to_string())?;
+    let channel_tuple = stmt.query_row([id], |row| {
+        Ok((
+            row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, Option<String>>(3)?, row.get::<_, String>(4)?,
+            row.get::<_, String>(5)?, row.get::<_, Option<i64>>(6)?, row.get::<_, bool>(7)?, row.get::<_, bool>(8)?, row.get::<_, i64>(9)?, row.get::<_, Option<i64>>(10)?,
+            row.get::<_, i64>(11)?,
+        ))
+    }).map_err(|e| e.to_string())?;
+
+    let mut epg_final = Vec::new();
+    let mut epg_stmt_safe = conn.prepare(&format!("SELECT {} FROM epg_entries WHERE channel_id = ?1", EPG_ENTRY_COLUMNS)).map_err(|e| e.to_string())?;
+    let rows = epg_stmt_safe.query_map([id], |row| map_row_to_epg_entry(row, 0)).map_err(|e| e.to_string())?;
+    for r in rows { epg_final.push(r.map_err(|e| e.to_string())?); }
+
+    Ok(Channel {
+        id: channel_tuple.0, playlist_id: channel_tuple.1, name: channel_tuple.2, logo_url: channel_tuple.3, stream_url: channel_tuple.4,
+        epg: epg_final, category: channel_tuple.5, category_id: channel_tuple.6, is_favorite: channel_tuple.7, is_hidden: channel_tuple.8,
+        catchup_days: channel_tuple.9, channel_number: channel_tuple.10, epg_shift: channel_tuple.11,
+    })
 }
 
 #[tauri::command]
-fn get_channels(options: FetchOptions, app: tauri::AppHandle) -> Result<PaginatedResponse<Channel>, String> {
+fn toggle_channel_favorite(id: i64, app: tauri::AppHandle) -> Result<Channel, String> {
     let conn = get_db_connection(&app)?;
-    let page = options.page.max(1);
-    let page_size = options.page_size;
-    let offset = (page - 1) * page_size;
-    
-    // 1. Determine Filter Mode
-    let mut is_favorites = false;
-    let mut is_recently_watched = false;
-    if let Some(obj) = options.filter.as_object() {
-        if let Some(type_val) = obj.get("type") {
-            match type_val.as_str() {
-                Some("favorites") => is_favorites = true,
-                Some("recently-watched") => is_recently_watched = true,
-                _ => {}
-            }
-        }
-    }
+    conn.execute("UPDATE channels SET is_favorite = NOT is_favorite WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+    fetch_single_channel(&conn, id)
+}
 
-    let has_search = !options.search_term.is_empty();
-    
-    // 2. Prepare Base Filter Values (We extract these so we can rebuild params later)
-    let show_hidden = options.show_hidden.unwrap_or(false);
-    
-    let mut category_filter_id: Option<i64> = None;
-    
-    // Category Logic (Only if NOT searching and NOT special views)
-    if !has_search && !is_favorites && !is_recently_watched {
-         if let Some(cat_id) = options.filter.as_i64() {
-             category_filter_id = Some(cat_id);
-         } 
-         else if let Some(obj) = options.filter.as_object() {
-            if let Some(val) = obj.get("categoryId") {
-                if !val.is_null() {
-                    if let Some(cat_id) = val.as_i64() {
-                        category_filter_id = Some(cat_id);
-                    }
-                }
-            }
+#[tauri::command]
+fn toggle_channel_visibility(id: i64, app: tauri::AppHandle) -> Result<Channel, String> {
+    let conn = get_db_connection(&app)?;
+    conn.execute("UPDATE channels SET is_hidden = NOT is_hidden WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+    fetch_single_channel(&conn, id)
+}
+
+// Sets how many minutes a channel's guide is moved by. Programmes already stored for the
+// channel are moved by the difference, so the guide is right before the next EPG refresh.
+#[tauri::command]
+fn set_channel_epg_shift(id: i64, minutes: i64, app: tauri::AppHandle) -> Result<Channel, String> {
+    let minutes = validate_time_offset(minutes)?;
+    let mut conn = get_db_connection(&app)?;
+    let previous: i64 = conn.query_row("SELECT epg_shift FROM channels WHERE id = ?1", [id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    let delta = chrono::Duration::minutes(minutes - previous);
+
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    tx.execute("UPDATE channels SET epg_shift = ?1 WHERE id = ?2", [minutes, id]).map_err(|e| e.to_string())?;
+    if minutes != previous {
+        let entries = {
+            // Moved in the direction of the shift so no row lands on one that has not moved yet
+            let order = if minutes > previous { "DESC" } else { "ASC" };
+            let mut stmt = tx.prepare(&format!("SELECT id, start_time, end_time FROM epg_entries WHERE channel_id = ?1 ORDER BY start_time {}", order)).map_err(|e| e.to_string())?;
+            let rows = stmt.query_map([id], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, String>(2)?))).map_err(|e| e.to_string())?;
+            rows.collect::<Result<Vec<_>, _>>().map_err(|e| e.to_string())?
+        };
+        let shift = |time: &str| chrono::DateTime::parse_from_rfc3339(time).map_or_else(|_| time.to_string(), |t| (t.with_timezone(&chrono::Utc) + delta).to_rfc3339());
+        let mut stmt = tx.prepare("UPDATE epg_entries SET start_time = ?1, end_time = ?2 WHERE id = ?3").map_err(|e| e.to_string())?;
+        for (entry_id, start, end) in &entries {
+            stmt.execute(rusqlite::params![shift(start), shift(end), entry_id]).map_err(|e| e.to_string())?;
         }
     }
+    tx.commit().map_err(|e| e.to_string())?;
+    fetch_single_channel(&conn, id)
+}
 
-    // 3. Build Base SQL String
-    let mut base_where = Vec::new();
-    if is_favorites { base_where.push("is_favorite = ?"); }
-    base_where.push("is_hidden = ?");
-    if category_filter_id.is_some() {
-        base_where.push("(category_id = ? OR category = (SELECT name FROM categories WHERE id = ?))");
-    }
+#[tauri::command]
+fn batch_update_channel_visibility(ids: Vec<i64>, is_hidden: bool, app: tauri::AppHandle) -> Result<Vec<Channel>, String> {
+    let conn = get_db_connection(&app)?;
+    if ids.is_empty() { return Ok(vec![]); }
+    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let sql = format!("UPDATE channels SET is_hidden = ?1 WHERE id IN ({})", placeholders);
+    let mut params: Vec<rusqlite::types::Value> = vec![is_hidden.into()];
+    params.extend(ids.iter().map(|&id| id.into()));
+    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
+    Ok(vec![]) 
+}
+
+#[tauri::command]
+fn batch_update_channel_favorite_status(ids: Vec<i64>, is_favorite: bool, app: tauri::AppHandle) -> Result<Vec<Channel>, String> {
+    let conn = get_db_connection(&app)?;
+    if ids.is_empty() { return Ok(vec![]); }
+    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    let sql = format!("UPDATE channels SET is_favorite = ?1 WHERE id IN ({})", placeholders);
+    let mut params: Vec<rusqlite::types::Value> = vec![is_favorite.into()];
+    params.extend(ids.iter().map(|&id| id.into()));
+    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
+    Ok(vec![])
+}
+
+#[tauri::command]
+fn add_to_recently_watched(channel_id: i64, app: tauri::AppHandle) -> Result<(), String> {
+    let conn = get_db_connection(&app)?;
+    let now = chrono::Utc::now().to_rfc3339();
     
-    let base_where_sql = if base_where.is_empty() { String::new() } else { format!("WHERE {}", base_where.join(" AND ")) };
+    // 1. Upsert the record (Insert or Update timestamp)
+    conn.execute(
+        "INSERT OR REPLACE INTO recently_watched (channel_id, last_watched) VALUES (?1, ?2)",
+        rusqlite::params![channel_id, now],
+    ).map_err(|e| e.to_string())?;
 
-    // Helper to generate a fresh param vector matching base_where_sql
-    // We cannot clone Box<dyn ToSql>, so we must reconstruct it.
-    let get_base_params = || -> Vec<Box<dyn rusqlite::ToSql>> {
-        let mut p: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
-        if is_favorites { p.push(Box::new(true)); }
-        p.push(Box::new(show_hidden));
-        if let Some(cid) = category_filter_id {
-            p.push(Box::new(cid));
-            p.push(Box::new(cid));
-        }
-        p
+    // 2. Prune: Keep only top 15
+    conn.execute(
+        "DELETE FROM recently_watched WHERE channel_id NOT IN (
+            SELECT channel_id FROM recently_watched ORDER BY last_watched DESC LIMIT 15
+        )",
+        [],
+    ).map_err(|e| e.to_string())?;
+
+    Ok(())
+}
+#[tauri::command]
+fn toggle_vod_watchlist(id: i64, r#type: String, app: tauri::AppHandle) -> Result<VodItem, String> {
+    let conn = get_db_connection(&app)?;
+    toggle_vod_flag(&conn, "vod_watchlist", &r#type, id)
+}
+
+#[tauri::command]
+fn toggle_vod_favorite(id: i64, r#type: String, app: tauri::AppHandle) -> Result<VodItem, String> {
+    let conn = get_db_connection(&app)?;
+    toggle_vod_flag(&conn, "vod_favorites", &r#type, id)
+}
+
+// Base URL of the Xtream server of `playlist` that `url` was built on
+fn xtream_server_of(playlist: &Playlist, url: &str) -> Option<String> {
+    if playlist.r#type != "xtream" { return None; }
+    playlist.server_urls().iter().map(|u| xtream_base_url(u)).find(|b| url.starts_with(b.as_str()))
+}
+
+// Checks that an Xtream server is up through its account endpoint. Opening the stream itself
+// would take one of the account's connection slots and could get the real playback refused.
+async fn probe_xtream_server(client: &Client, playlist: &Playlist) -> Result<(), RefreshError> {
+    let account = XtreamAccount::from_playlist(playlist)?;
+    let resp = client.get(account.account_url()).timeout(std::time::Duration::from_secs(10)).send().await.map_err(request_error)?;
+    if !resp.status().is_success() { return Err(status_error(resp.status())); }
+    Ok(())
+}
+
+// Plays a stream of `playlist_id`. The UI names the playlist, since the same URL can be listed by
+// several playlists that differ in HTTP profile, portal or backup servers.
+#[tauri::command]
+async fn play_stream(url: String, playlist_id: i64, app: tauri::AppHandle) -> Result<(), String> {
+    let playlist = {
+        let conn = get_db_connection(&app)?;
+        get_playlist_by_id(&conn, playlist_id).ok_or_else(|| format!("Playlist {} not found", playlist_id))?
     };
+    // Requests and mpv use the HTTP profile of the playlist the stream belongs to
+    let profile = playlist.http_profile.clone();
+    let client = build_http_client(&profile)?;
+    let client_ref = &client;
 
-    // Variables for results
-    let final_ids_for_page: Vec<i64>;
-    let total_count: i64;
+    let url = if playlist.r#type == "stalker" {
+        // Stalker channels store the portal `cmd`, which must be exchanged for a fresh link
+        let cmd = url.as_str();
+        with_server_failover(&app, &playlist, |candidate| async move {
+            let session = StalkerSession::connect(client_ref, &candidate).await?;
+            session.create_link(cmd).await
+        }).await?.1
+    } else if let Some(base) = xtream_server_of(&playlist, &url).filter(|_| playlist.server_urls().len() > 1) {
+        // Xtream URLs carry the server they were built on; with backups configured, the servers are
+        // checked starting from the one that last worked, and the stream is played from the first one up
+        let path = url[base.len()..].to_string();
+        let path = path.as_str();
+        with_server_failover(&app, &playlist, |candidate| async move {
+            probe_xtream_server(client_ref, &candidate).await?;
+            Ok(format!("{}{}", xtream_base_url(&candidate.url), path))
+        }).await?.1
+    } else {
+        url
+    };
 
-    if has_search {
-        // --- SEARCH LOGIC (Deep Search) ---
-        let search_pattern = format!("%{}%", options.search_term);
-        let now = chrono::Utc::now().to_rfc3339();
+    launch_mpv(&app, &url, &profile)
+}
 
-        // Query A: Channels matching Name (Applied on top of base filters)
-        let sql_a_where = if base_where_sql.is_empty() { "WHERE name LIKE ?".to_string() } else { format!("{} AND name LIKE ?", base_where_sql) };
-        let sql_a = format!("SELECT id FROM channels {}", sql_a_where);
-        
-        // Query B: EPG Matches (Current Program)
-        let sql_b = "SELECT DISTINCT channel_id FROM epg_entries WHERE title LIKE ? AND start_time <= ? AND end_time > ?";
+// Zaps to a channel by its provider number. Visible channels of active playlists win;
+// `playlist_id` narrows the lookup when several playlists share a numbering.
+#[tauri::command]
+async fn tune_channel_by_number(number: i64, playlist_id: Option<i64>, app: tauri::AppHandle) -> Result<Channel, String> {
+    let channel = {
+        let conn = get_db_connection(&app)?;
+        let channel_id: i64 = conn.query_row(
+            "SELECT c.id FROM channels c JOIN playlists p ON p.id = c.playlist_id
+             WHERE c.channel_number = ?1 AND p.is_active = true AND c.is_hidden = false AND (?2 IS NULL OR c.playlist_id = ?2)
+             ORDER BY p.id, c.sort_order LIMIT 1",
+            rusqlite::params![number, playlist_id],
+            |row| row.get(0),
+        ).map_err(|e| match e {
+            rusqlite::Error::QueryReturnedNoRows => format!("No channel with number {}", number),
+            other => other.to_string(),
+        })?;
+        fetch_single_channel(&conn, channel_id)?
+    };
 
-        let mut all_valid_ids: Vec<i64> = Vec::new();
+    play_stream(channel.stream_url.clone(), channel.playlist_id, app).await?;
+    Ok(channel)
+}
 
-        // Run A (Name Match)
-        let mut params_a = get_base_params(); // Fresh Params
-        params_a.push(Box::new(search_pattern.clone())); // Add Search Param
-        
-        let mut stmt_a = conn.prepare(&sql_a).map_err(|e| e.to_string())?;
-        let ids_a = stmt_a.query_map(rusqlite::params_from_iter(params_a.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
-        for id in ids_a { all_valid_ids.push(id.unwrap_or(0)); }
+fn launch_mpv(app: &tauri::AppHandle, url: &str, profile: &HttpProfile) -> Result<(), String> {
+    use tauri_plugin_shell::ShellExt;
 
-        // Run B (EPG Match)
-        let mut stmt_b = conn.prepare(sql_b).map_err(|e| e.to_string())?;
-        let ids_b = stmt_b.query_map(rusqlite::params![search_pattern, now, now], |row| row.get(0)).map_err(|e| e.to_string())?;
-        for id in ids_b { all_valid_ids.push(id.unwrap_or(0)); }
+    println!("[Player] Attempting to play: {}", url);
 
-        // Deduplicate
-        all_valid_ids.sort();
-        all_valid_ids.dedup();
+    let mut args = vec![
+        "--force-window".to_string(),   // Open window immediately (don't wait for buffer)
+        "--geometry=60%".to_string(),   // Sets window width to 60% of screen (maintains aspect ratio)
+    ];
+    // Providers that whitelist user agents or require headers check the player's requests too
+    if let Some(user_agent) = profile.custom_user_agent() {
+        args.push(format!("--user-agent={}", user_agent));
+    }
+    for header in &profile.headers {
+        // The -append form takes one entry, so commas inside the value are kept
+        args.push(format!("--http-header-fields-append={}: {}", header.name.trim(), header.value.trim()));
+    }
+    // mpv only speaks HTTP proxies; SOCKS proxies apply to the app's own requests only.
+    // Certificates need no flag: mpv does not verify them unless told to.
+    if let Some(proxy) = profile.proxy_url().filter(|p| p.starts_with("http://")) {
+        args.push(format!("--http-proxy={}", proxy));
+    }
+    args.push(url.to_string());         // The stream URL
 
-        // Verify base filters for EPG results
-        // (Query B results didn't check hidden/favorites yet, we must verify them)
-        if !all_valid_ids.is_empty() {
-            let id_list = all_valid_ids.iter().map(|i| i.to_string()).collect::<Vec<_>>().join(",");
-            
-            let verify_sql = format!("SELECT id FROM channels {} AND id IN ({})", 
-                if base_where_sql.is_empty() { "WHERE 1=1".to_string() } else { base_where_sql.clone() }, 
-                id_list
-            );
-            
-            let mut stmt_verify = conn.prepare(&verify_sql).map_err(|e| e.to_string())?;
-            let params_verify = get_base_params(); // Fresh Params for verification
-            
-            let verified_iter = stmt_verify.query_map(rusqlite::params_from_iter(params_verify.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
-            
-            all_valid_ids = verified_iter.filter_map(|r| r.ok()).collect();
-        }
+    // "mpv" matches the binary name, NOT the path in tauri.conf.json
+    let _child = app.shell().sidecar("mpv")
+        .map_err(|e| format!("Failed to find sidecar: {}", e))? 
+        .args(args)
+        .spawn()
+        .map_err(|e| format!("Failed to launch MPV: {}", e))?;
 
-        total_count = all_valid_ids.len() as i64;
+    Ok(())
+}
+
+// --- CATCH-UP / TV ARCHIVE ---
+// Past programmes of a channel that are still inside the provider's archive window
+#[tauri::command]
+fn get_catchup_programmes(channel_id: i64, app: tauri::AppHandle) -> Result<Vec<EpgEntry>, String> {
+    let conn = get_db_connection(&app)?;
+    let catchup_days: i64 = conn.query_row("SELECT catchup_days FROM channels WHERE id = ?1", [channel_id], |row| row.get(0)).map_err(|e| e.to_string())?;
+    if catchup_days <= 0 { return Ok(vec![]); }
+
+    let now = chrono::Utc::now();
+    let window_start = (now - chrono::Duration::days(catchup_days)).to_rfc3339();
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {} FROM epg_entries
+         WHERE channel_id = ?1 AND start_time >= ?2 AND end_time <= ?3 ORDER BY start_time DESC", EPG_ENTRY_COLUMNS
+    )).map_err(|e| e.to_string())?;
+    let entries = stmt.query_map(rusqlite::params![channel_id, window_start, now.to_rfc3339()], |row| map_row_to_epg_entry(row, 0))
+        .map_err(|e| e.to_string())?.collect::<Result<Vec<EpgEntry>, _>>().map_err(|e| e.to_string())?;
+    Ok(entries)
+}
 
-        // Manual Pagination in Memory
-        let start = offset as usize;
-        if start >= all_valid_ids.len() {
-            final_ids_for_page = Vec::new();
-        } else {
-            let end = (start + page_size as usize).min(all_valid_ids.len());
-            final_ids_for_page = all_valid_ids[start..end].to_vec();
-        }
+// Builds the Xtream timeshift URL for a past programme and hands it to mpv.
+// `url_style` "php" selects the streaming/timeshift.php form some panels require.
+#[tauri::command]
+async fn play_catchup(channel_id: i64, start_time: String, end_time: String, url_style: Option<String>, app: tauri::AppHandle) -> Result<(), String> {
+    let (playlist_id, url) = catchup_url(channel_id, &start_time, &end_time, url_style.as_deref(), &app)?;
+    // Goes through play_stream so the server failover applies to the archive too
+    play_stream(url, playlist_id, app).await
+}
 
-    } else {
-        // --- BROWSE LOGIC (Standard) ---
-        let table = if is_recently_watched { "channels c JOIN recently_watched rw ON c.id = rw.channel_id" } else { "channels c" };
-        
-        let order_sql = if is_recently_watched { 
-            String::from("ORDER BY rw.last_watched DESC")
-        } else { 
-            let col = if options.sort_by == "name" { "c.name" } else { "c.sort_order" };
-            let dir = if options.sort_order == "desc" { "DESC" } else { "ASC" };
-            format!("ORDER BY {} {}", col, dir)
-        };
+// The timeshift URL, together with the playlist of the channel
+fn catchup_url(channel_id: i64, start_time: &str, end_time: &str, url_style: Option<&str>, app: &tauri::AppHandle) -> Result<(i64, String), String> {
+    let conn = get_db_connection(app)?;
+    let (playlist_id, stream_id, catchup_days) = conn.query_row(
+        "SELECT playlist_id, remote_id, catchup_days FROM channels WHERE id = ?1",
+        [channel_id],
+        |row| Ok((row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, i64>(2)?))
+    ).map_err(|e| e.to_string())?;
+    if catchup_days <= 0 { return Err("This channel has no TV archive".to_string()); }
 
-        // 1. Count Total
-        let params_count = get_base_params(); // Fresh Params
-        let count_sql = format!("SELECT COUNT(*) FROM {} {}", table, base_where_sql);
-        total_count = conn.query_row(&count_sql, rusqlite::params_from_iter(params_count.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
-        
-        // 2. Fetch Paginated IDs
-        let mut params_fetch = get_base_params(); // Fresh Params
-        params_fetch.push(Box::new(page_size));
-        params_fetch.push(Box::new(offset));
+    let playlist = get_playlist_by_id(&conn, playlist_id).ok_or_else(|| format!("Playlist {} not found", playlist_id))?;
+    // Built on the server that worked last; play_stream falls back to the others
+    let server = playlist.server_urls().into_iter().next().unwrap_or_default();
+    let account = XtreamAccount::from_playlist(&playlist.with_url(&server))?;
 
-        let fetch_sql = format!("SELECT c.id FROM {} {} {} LIMIT ? OFFSET ?", table, base_where_sql, order_sql);
-        
-        let mut stmt = conn.prepare(&fetch_sql).map_err(|e| e.to_string())?;
-        let rows = stmt.query_map(rusqlite::params_from_iter(params_fetch.iter().map(|p| p.as_ref())), |row| row.get(0)).map_err(|e| e.to_string())?;
-        
-        final_ids_for_page = rows.filter_map(|r| r.ok()).collect();
+    let start = chrono::DateTime::parse_from_rfc3339(start_time).map_err(|e| format!("Invalid start time: {}", e))?;
+    let end = chrono::DateTime::parse_from_rfc3339(end_time).map_err(|e| format!("Invalid end time: {}", e))?;
+    if start < chrono::Utc::now() - chrono::Duration::days(catchup_days) {
+        return Err("Programme is outside the archive window".to_string());
     }
+    let duration_minutes = ((end - start).num_seconds() + 59) / 60;
+    if duration_minutes <= 0 { return Err("Programme has no duration".to_string()); }
 
-    if final_ids_for_page.is_empty() {
-        return Ok(PaginatedResponse { items: vec![], has_more: false, total: total_count });
-    }
+    // Timeshift start times are interpreted in the server's local timezone
+    let start_str = match playlist.server_timezone.as_deref().and_then(|tz| tz.parse::<chrono_tz::Tz>().ok()) {
+        Some(tz) => start.with_timezone(&tz).format("%Y-%m-%d:%H-%M").to_string(),
+        None => start.with_timezone(&chrono::Utc).format("%Y-%m-%d:%H-%M").to_string(),
+    };
 
-    // --- HYDRATION STEP (Fetch Details for IDs) ---
-    let ids_params: Vec<rusqlite::types::Value> = final_ids_for_page.iter().map(|&id| id.into()).collect();
-    let placeholders = final_ids_for_page.iter().map(|_| "?").collect::<Vec<_>>().join(",");
+    Ok((playlist_id, account.timeshift_url(stream_id, &start_str, duration_minutes, url_style == Some("php"))))
+}
 
-    // 1. Fetch EPG
-    let mut epg_map: HashMap<i64, Vec<EpgEntry>> = HashMap::new();
-    let epg_sql = format!("SELECT channel_id, id, title, description, start_time, end_time FROM epg_entries WHERE channel_id IN ({})", placeholders);
-    let mut epg_stmt = conn.prepare(&epg_sql).map_err(|e| e.to_string())?;
-    let epg_rows = epg_stmt.query_map(rusqlite::params_from_iter(ids_params.clone()), |row| {
-        Ok((row.get::<_, i64>(0)?, EpgEntry { id: row.get(1)?, title: row.get(2)?, description: row.get(3)?, start_time: row.get(4)?, end_time: row.get(5)? }))
-    }).map_err(|e| e.to_string())?;
-    for row in epg_rows {
-        if let Ok((cid, entry)) = row { epg_map.entry(cid).or_default().push(entry); }
+// Reads an XMLTV timestamp. Times without an offset are in `timezone` (the provider's
+// server timezone when known), or UTC.
+fn parse_xmltv_time(xmltv_date: &str, timezone: Option<chrono_tz::Tz>) -> Option<chrono::DateTime<chrono::Utc>> {
+    // Try format with space: "20241118120000 +0000"
+    if let Ok(dt) = chrono::DateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S %z") {
+        return Some(dt.with_timezone(&chrono::Utc));
+    }
+    // Try format without space: "20241118120000+0000"
+    if let Ok(dt) = chrono::DateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S%z") {
+        return Some(dt.with_timezone(&chrono::Utc));
+    }
+    let dt = chrono::NaiveDateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S").ok()?;
+    // Fallback: Assume UTC if no timezone provided
+    let Some(tz) = timezone else { return Some(dt.and_utc()) };
+    match chrono::TimeZone::from_local_datetime(&tz, &dt).earliest() {
+        // The earlier of the two readings when a time repeats as the clocks go back
+        Some(local) => Some(local.with_timezone(&chrono::Utc)),
+        // A time skipped as the clocks go forward reads with the offset from before the change
+        None => {
+            let offset = chrono::Offset::fix(&chrono::TimeZone::offset_from_utc_datetime(&tz, &dt));
+            Some(dt.and_utc() - chrono::Duration::seconds(i64::from(offset.local_minus_utc())))
+        },
     }
+}
 
-    // 2. Fetch Channels
-    let chan_sql = format!("SELECT id, playlist_id, name, logo_url, stream_url, category, category_id, is_favorite, is_hidden FROM channels WHERE id IN ({})", placeholders);
-    let mut final_stmt = conn.prepare(&chan_sql).map_err(|e| e.to_string())?;
-    let mut channels: Vec<Channel> = final_stmt.query_map(rusqlite::params_from_iter(ids_params), |row| {
-        let channel_id: i64 = row.get(0)?;
-        Ok(Channel {
-            id: channel_id, playlist_id: row.get(1)?, name: row.get(2)?, logo_url: row.get(3)?, stream_url: row.get(4)?,
-            epg: epg_map.remove(&channel_id).unwrap_or_default(), category: row.get(5)?, category_id: row.get(6)?, is_favorite: row.get(7)?, is_hidden: row.get(8)?,
-        })
-    }).map_err(|e| e.to_string())?.collect::<Result<Vec<Channel>, _>>().map_err(|e| e.to_string())?;
+// Time shifts are kept within a day either way
+fn validate_time_offset(minutes: i64) -> Result<i64, String> {
+    if minutes.abs() > 24 * 60 { return Err(format!("Time offset of {} minutes is more than 24 hours", minutes)); }
+    Ok(minutes)
+}
 
-    // Restore Order
-    let mut pos_map = HashMap::new();
-    for (i, id) in final_ids_for_page.iter().enumerate() { pos_map.insert(*id, i); }
-    channels.sort_by_key(|c| pos_map.get(&c.id).unwrap_or(&0));
+// --- EPG SOURCES ---
 
-    Ok(PaginatedResponse { items: channels, has_more: (page * page_size) < total_count, total: total_count })
+// A user-managed XMLTV guide: a URL or a local file, for every playlist or just one
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct EpgSource {
+    id: i64,
+    name: String,
+    // http(s) URL or path of a local .xml / .xml.gz / .xz file
+    url: String,
+    // None applies the guide to the channels of every active playlist
+    playlist_id: Option<i64>,
+    // Lower numbers go first; a channel keeps the programmes of the first source that covers it
+    priority: i64,
+    is_active: bool,
+    status: String,
+    error_message: Option<String>,
+    last_updated: Option<String>,
+    // Minutes added to every programme time of this guide
+    time_offset: i64,
+}
+
+#[derive(serde::Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct NewEpgSource {
+    name: String,
+    url: String,
+    playlist_id: Option<i64>,
+    #[serde(default)]
+    priority: i64,
+    #[serde(default)]
+    time_offset: i64,
+}
+
+fn map_row_to_epg_source(row: &Row) -> rusqlite::Result<EpgSource> {
+    Ok(EpgSource {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        url: row.get(2)?,
+        playlist_id: row.get(3)?,
+        priority: row.get(4)?,
+        is_active: row.get(5)?,
+        status: row.get(6)?,
+        error_message: row.get(7)?,
+        last_updated: row.get(8)?,
+        time_offset: row.get(9)?,
+    })
+}
+
+fn is_remote_source(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn validate_epg_source_url(url: &str) -> Result<String, String> {
+    let url = url.trim();
+    if url.is_empty() { return Err("The guide URL or file path is empty".to_string()); }
+    if is_remote_source(url) {
+        reqwest::Url::parse(url).map_err(|e| format!("Invalid URL: {}", e))?;
+    } else {
+        let path = url.strip_prefix("file://").unwrap_or(url);
+        if !std::path::Path::new(path).is_file() { return Err(format!("File not found: {}", path)); }
+    }
+    Ok(url.to_string())
 }
 
 #[tauri::command]
-fn get_movies(_options: Value) -> Result<Value, String> { Ok(json!({ "items": [], "hasMore": false, "total": 0 })) }
-#[tauri::command]
-fn get_series(_options: Value) -> Result<Value, String> { Ok(json!({ "items": [], "hasMore": false, "total": 0 })) }
-#[tauri::command]
-fn get_seasons_for_series(_series_id: Value) -> Result<Value, String> { Ok(json!([])) }
-#[tauri::command]
-fn get_categories(app: tauri::AppHandle) -> Result<Vec<Category>, String> {
+fn get_epg_sources(app: tauri::AppHandle) -> Result<Vec<EpgSource>, String> {
     let conn = get_db_connection(&app)?;
-    let mut stmt = conn.prepare("SELECT * FROM categories").map_err(|e| e.to_string())?;
-    let categories = stmt.query_map([], map_row_to_category).map_err(|e| e.to_string())?.collect::<Result<Vec<Category>, _>>().map_err(|e| e.to_string())?;
-    Ok(categories)
+    let mut stmt = conn.prepare("SELECT * FROM epg_sources ORDER BY priority, id").map_err(|e| e.to_string())?;
+    let sources = stmt.query_map([], map_row_to_epg_source).map_err(|e| e.to_string())?.collect::<Result<Vec<EpgSource>, _>>().map_err(|e| e.to_string())?;
+    Ok(sources)
 }
+
 #[tauri::command]
-fn add_playlist(playlist_data: NewPlaylistData, app: tauri::AppHandle) -> Result<Playlist, String> {
+fn add_epg_source(source: NewEpgSource, app: tauri::AppHandle) -> Result<EpgSource, String> {
     let conn = get_db_connection(&app)?;
-    conn.execute("INSERT INTO playlists (name, url, type, error_message, username, password, mac_address, max_connections, expiration_date, last_updated, status, is_active) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, 'inactive', true)",
-        rusqlite::params![playlist_data.name, playlist_data.url, playlist_data.r#type, playlist_data.error_message, playlist_data.username, playlist_data.password, playlist_data.mac_address, playlist_data.max_connections, playlist_data.expiration_date, playlist_data.last_updated],
+    let url = validate_epg_source_url(&source.url)?;
+    let time_offset = validate_time_offset(source.time_offset)?;
+    conn.execute(
+        "INSERT INTO epg_sources (name, url, playlist_id, priority, is_active, status, time_offset) VALUES (?1, ?2, ?3, ?4, true, 'inactive', ?5)",
+        rusqlite::params![source.name.trim(), url, source.playlist_id, source.priority, time_offset],
     ).map_err(|e| e.to_string())?;
-    let new_id = conn.last_insert_rowid();
-    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").map_err(|e| e.to_string())?;
-    let new_playlist = stmt.query_row(rusqlite::params![new_id], map_row_to_playlist).map_err(|e| e.to_string())?;
-    Ok(new_playlist)
+    conn.query_row("SELECT * FROM epg_sources WHERE id = ?1", [conn.last_insert_rowid()], map_row_to_epg_source).map_err(|e| e.to_string())
 }
+
 #[tauri::command]
-fn update_playlist(playlist: Playlist, app: tauri::AppHandle) -> Result<Playlist, String> {
+fn update_epg_source(mut source: EpgSource, app: tauri::AppHandle) -> Result<EpgSource, String> {
     let conn = get_db_connection(&app)?;
-    conn.execute("UPDATE playlists SET name = ?1, url = ?2, type = ?3, is_active = ?4, status = ?5, error_message = ?6, username = ?7, password = ?8, mac_address = ?9, max_connections = ?10, expiration_date = ?11, last_updated = ?12 WHERE id = ?13",
-        rusqlite::params![playlist.name, playlist.url, playlist.r#type, playlist.is_active, playlist.status, playlist.error_message, playlist.username, playlist.password, playlist.mac_address, playlist.max_connections, playlist.expiration_date, playlist.last_updated, playlist.id],
+    source.url = validate_epg_source_url(&source.url)?;
+    validate_time_offset(source.time_offset)?;
+    conn.execute(
+        "UPDATE epg_sources SET name = ?1, url = ?2, playlist_id = ?3, priority = ?4, is_active = ?5, time_offset = ?6 WHERE id = ?7",
+        rusqlite::params![source.name.trim(), source.url, source.playlist_id, source.priority, source.is_active, source.time_offset, source.id],
     ).map_err(|e| e.to_string())?;
-    Ok(playlist) 
+    conn.query_row("SELECT * FROM epg_sources WHERE id = ?1", [source.id], map_row_to_epg_source).map_err(|e| e.to_string())
 }
+
 #[tauri::command]
-fn delete_playlist(id: i64, app: tauri::AppHandle) -> Result<(), String> {
+fn delete_epg_source(id: i64, app: tauri::AppHandle) -> Result<(), String> {
     let conn = get_db_connection(&app)?;
-    conn.execute("DELETE FROM playlists WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM epg_sources WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
     Ok(())
 }
-#[tauri::command]
-fn update_playlist_active_status(id: i64, is_active: bool, app: tauri::AppHandle) -> Result<Playlist, String> {
-    let conn = get_db_connection(&app)?;
-    conn.execute("UPDATE playlists SET is_active = ?1 WHERE id = ?2", rusqlite::params![is_active, id]).map_err(|e| e.to_string())?;
-    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE id = ?1").map_err(|e| e.to_string())?;
-    let updated_playlist = stmt.query_row(rusqlite::params![id], map_row_to_playlist).map_err(|e| e.to_string())?;
-    Ok(updated_playlist)
+
+// Where one guide of an EPG refresh comes from
+enum GuideOrigin {
+    Source(EpgSource),
+    // The provider's own xmltv.php of an Xtream playlist
+    Provider(Box<Playlist>),
 }
 
-#[tauri::command]
-async fn refresh_playlist(playlist_id: i64, app: tauri::AppHandle) -> Result<(), String> {
-    let start_time = std::time::Instant::now();
-    println!("[DEBUG] Starting refresh for playlist ID: {}", playlist_id);
+// How the times of one guide are read: the minutes added to every programme (global
+// setting plus the source's own offset), the timezone of times given without an offset,
+// and the window of programmes that are kept (None keeps everything)
+#[derive(Clone, Copy, Default)]
+struct GuideTiming {
+    offset_minutes: i64,
+    timezone: Option<chrono_tz::Tz>,
+    retention: Option<(chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>)>,
+}
 
-    let mut conn = get_db_connection(&app)?;
-    
-    let (base_url, username, password) = conn.query_row(
-        "SELECT url, username, password FROM playlists WHERE id = ?1",
-        [playlist_id],
-        |row| Ok((row.get::<_, String>(0)?, row.get::<_, Option<String>>(1)?, row.get::<_, Option<String>>(2)?))
+// One guide to import, and the playlist whose channels it may fill (None: all active playlists)
+struct GuideJob {
+    label: String,
+    // Tags the rows of this guide in epg_entries
+    key: String,
+    scope: Option<i64>,
+    origin: GuideOrigin,
+    timing: GuideTiming,
+}
+
+// User sources go first in priority order, then the guides of the Xtream providers
+fn collect_guide_jobs(conn: &rusqlite::Connection) -> Result<Vec<GuideJob>, String> {
+    let settings = read_settings(conn)?;
+    let global_offset = settings.as_ref().map_or(0, |settings| i64::from(settings.epg_time_offset));
+    let retention = Some(guide_retention(settings.as_ref()));
+    let parse_tz = |tz: Option<&str>| tz.and_then(|tz| tz.parse::<chrono_tz::Tz>().ok());
+
+    let mut jobs: Vec<GuideJob> = Vec::new();
+    let mut stmt = conn.prepare(
+        "SELECT s.*, p.server_timezone FROM epg_sources s LEFT JOIN playlists p ON p.id = s.playlist_id
+         WHERE s.is_active = true AND (s.playlist_id IS NULL OR p.is_active = true) ORDER BY s.priority, s.id"
     ).map_err(|e| e.to_string())?;
+    let sources = stmt.query_map([], |row| Ok((map_row_to_epg_source(row)?, row.get::<_, Option<String>>(10)?))).map_err(|e| e.to_string())?;
+    for source in sources {
+        let (source, server_timezone) = source.map_err(|e| e.to_string())?;
+        // A source tied to a playlist reads naive times in that provider's timezone
+        let timing = GuideTiming { offset_minutes: global_offset + source.time_offset, timezone: parse_tz(server_timezone.as_deref()), retention };
+        jobs.push(GuideJob {
+            label: format!("Source {} ({})", source.id, source.name), key: format!("source:{}", source.id),
+            scope: source.playlist_id, origin: GuideOrigin::Source(source), timing,
+        });
+    }
 
-    let (username, password) = match (username, password) {
-        (Some(u), Some(p)) => (u.trim().to_string(), p.trim().to_string()),
-        _ => return Err("Missing credentials".to_string()),
-    };
+    let mut stmt = conn.prepare("SELECT * FROM playlists WHERE is_active = true AND type = 'xtream'").map_err(|e| e.to_string())?;
+    for playlist in stmt.query_map([], map_row_to_playlist).map_err(|e| e.to_string())? {
+        let playlist = playlist.map_err(|e| e.to_string())?;
+        if playlist.username.is_none() || playlist.password.is_none() { continue; }
+        let timing = GuideTiming { offset_minutes: global_offset, timezone: parse_tz(playlist.server_timezone.as_deref()), retention };
+        jobs.push(GuideJob {
+            label: format!("Playlist {}", playlist.id), key: format!("playlist:{}", playlist.id),
+            scope: Some(playlist.id), origin: GuideOrigin::Provider(Box::new(playlist)), timing,
+        });
+    }
+    Ok(jobs)
+}
 
-    // --- PRESERVE STATE (Favorites & Hidden) ---
-    let mut existing_state: HashMap<i64, (bool, bool)> = HashMap::new();
-    {
-        let mut stmt = conn.prepare("SELECT id, is_favorite, is_hidden FROM channels WHERE playlist_id = ?1").map_err(|e| e.to_string())?;
-        let rows = stmt.query_map([playlist_id], |row| {
-            Ok((row.get::<_, i64>(0)?, row.get::<_, bool>(1)?, row.get::<_, bool>(2)?))
-        }).map_err(|e| e.to_string())?;
-        for row in rows {
-            if let Ok((id, fav, hid)) = row { existing_state.insert(id, (fav, hid)); }
-        }
+// The programmes a refresh keeps: from `epg_past_days` ago (for catch-up) to `epg_future_days` ahead
+fn guide_retention(settings: Option<&AppSettings>) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+    let past = settings.map_or_else(default_epg_past_days, |s| s.epg_past_days);
+    let future = settings.map_or_else(default_epg_future_days, |s| s.epg_future_days);
+    let now = chrono::Utc::now();
+    (now - chrono::Duration::days(i64::from(past)), now + chrono::Duration::days(i64::from(future)))
+}
+
+fn set_epg_source_status(conn: &rusqlite::Connection, source_id: i64, error: Option<&str>) -> Result<(), String> {
+    conn.execute(
+        "UPDATE epg_sources SET status = ?1, error_message = ?2, last_updated = ?3 WHERE id = ?4",
+        rusqlite::params![if error.is_some() { "error" } else { "active" }, error, chrono::Utc::now().to_rfc3339(), source_id],
+    ).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+// Starts the download of a remote guide. The body is streamed afterwards, so only the
+// request itself fails over between the servers of a playlist.
+async fn open_remote_guide(app: &tauri::AppHandle, job: &GuideJob) -> Result<reqwest::Response, String> {
+    match &job.origin {
+        GuideOrigin::Provider(playlist) => {
+            let client = build_http_client(&playlist.http_profile)?;
+            let (username, password) = (playlist.username.as_deref().unwrap_or_default(), playlist.password.as_deref().unwrap_or_default());
+            // Each server of the playlist is tried in turn until one hands out the guide
+            let client_ref = &client;
+            let (_, resp) = with_server_failover(app, playlist, |candidate| async move {
+                let epg_url = format!("{}xmltv.php?username={}&password={}", xtream_base_url(&candidate.url), username, password);
+                println!("[EPG] Fetching XML: {}", epg_url);
+                send_with_retry(client_ref.get(&epg_url)).await
+            }).await?;
+            Ok(resp)
+        },
+        GuideOrigin::Source(source) => {
+            // A source tied to a playlist is fetched with that playlist's HTTP profile
+            let profile = match source.playlist_id {
+                Some(id) => get_db_connection(app).ok().and_then(|conn| get_playlist_by_id(&conn, id)).map(|p| p.http_profile).unwrap_or_default(),
+                None => HttpProfile::default(),
+            };
+            println!("[EPG] Fetching XML: {}", source.url);
+            Ok(send_with_retry(build_http_client(&profile)?.get(&source.url)).await?)
+        },
     }
+}
 
-    // --- NEW: PRESERVE HISTORY (Recently Watched) ---
-    let mut recent_history: Vec<(i64, String)> = Vec::new();
-    {
-        // We collect history for channels belonging to this playlist (via join or check)
-        // Since IDs are unique per line, we can just grab them all and filter on re-insert, 
-        // or simplified: grab all history. Since we delete channels for THIS playlist, 
-        // only history for THIS playlist is at risk of cascade delete.
-        // To be safe, we just grab all. 
-        let mut stmt = conn.prepare("SELECT channel_id, last_watched FROM recently_watched").map_err(|e| e.to_string())?;
-        let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).map_err(|e| e.to_string())?;
-        for row in rows { if let Ok(pair) = row { recent_history.push(pair); } }
+// Path of a guide that is read from disk instead of downloaded
+fn local_guide_path(job: &GuideJob) -> Option<String> {
+    match &job.origin {
+        GuideOrigin::Source(source) if !is_remote_source(&source.url) => Some(source.url.strip_prefix("file://").unwrap_or(&source.url).to_string()),
+        _ => None,
     }
-    
-    update_playlist_status(&conn, playlist_id, "loading", None)?;
-    let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default()); 
+}
 
-    let client = reqwest::Client::builder()
-        .user_agent("Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36")
-        .timeout(std::time::Duration::from_secs(120)) 
-        .build()
-        .map_err(|e| e.to_string())?;
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GuideCompression {
+    None,
+    Gzip,
+    Xz,
+}
 
-    let mut clean_url = base_url.trim().to_string();
-    if clean_url.ends_with("player_api.php") { clean_url = clean_url.replace("player_api.php", ""); }
-    if !clean_url.ends_with('/') { clean_url.push('/'); }
-    let player_api_url = format!("{}player_api.php?username={}&password={}", clean_url, username, password);
+// Guides are recognised by their first bytes; file names and Content-Type are often wrong
+fn detect_guide_compression(head: &[u8]) -> Result<GuideCompression, String> {
+    if head.starts_with(&[0x1F, 0x8B]) { return Ok(GuideCompression::Gzip); }
+    if head.starts_with(&[0xFD, b'7', b'z', b'X', b'Z', 0x00]) { return Ok(GuideCompression::Xz); }
+    let text = head.strip_prefix(&[0xEF, 0xBB, 0xBF]).unwrap_or(head);
+    match text.iter().find(|b| !b.is_ascii_whitespace()) {
+        Some(b'<') | None => Ok(GuideCompression::None),
+        Some(_) => Err(ErrorKind::Parse.error("not an XMLTV guide (expected XML, gzip or xz)").into()),
+    }
+}
 
-    let categories_url = format!("{}&action=get_live_categories", player_api_url);
-    println!("[DEBUG] Fetching Categories...");
-    let categories = match client.get(&categories_url).send().await {
-        Ok(resp) => resp.json::<Vec<XtreamCategory>>().await.map_err(|e| e.to_string())?,
-        Err(e) => return Err(e.to_string()),
-    };
+fn guide_reader<R: std::io::Read + Send + 'static>(compression: GuideCompression, inner: R) -> Box<dyn std::io::Read + Send> {
+    match compression {
+        GuideCompression::None => Box::new(inner),
+        // Multi-member aware, since some guides are concatenated gzip streams
+        GuideCompression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(inner)),
+        GuideCompression::Xz => Box::new(xz2::read::XzDecoder::new_multi_decoder(inner)),
+    }
+}
 
-    let streams_url = format!("{}&action=get_live_streams", player_api_url);
-    println!("[DEBUG] Fetching Streams...");
-    let stream_response = match client.get(&streams_url).send().await {
-        Ok(resp) => resp,
-        Err(e) => {
-            update_playlist_status(&conn, playlist_id, "error", Some(e.to_string()))?;
-            let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default());
-            return Err(e.to_string());
-        }
-    };
-    let streams = match stream_response.json::<Vec<XtreamLiveStream>>().await {
-        Ok(s) => s,
-        Err(e) => {
-            println!("[ERROR] Stream JSON Parse Failed: {}", e);
-            return Err(format!("Stream JSON Error: {}", e));
-        }
-    };
-    println!("[DEBUG] Parsed {} streams. Starting Transaction...", streams.len());
+// Sniffs the compression of a guide from its first bytes and returns a buffered reader
+// over the plain XML
+fn open_guide(mut inner: Box<dyn std::io::Read + Send>) -> Result<Box<dyn std::io::BufRead + Send>, String> {
+    use std::io::Read;
+    let mut head = Vec::with_capacity(16);
+    (&mut inner).take(16).read_to_end(&mut head).map_err(|e| e.to_string())?;
+    let compression = detect_guide_compression(&head)?;
+    let body = std::io::Cursor::new(head).chain(inner);
+    Ok(Box::new(std::io::BufReader::with_capacity(64 * 1024, guide_reader(compression, body))))
+}
 
-    let tx = conn.transaction().map_err(|e| e.to_string())?;
-    tx.execute("DELETE FROM categories WHERE playlist_id = ?1", [playlist_id]).map_err(|e| e.to_string())?;
-    // This DELETE triggers the CASCADE on recently_watched
-    tx.execute("DELETE FROM channels WHERE playlist_id = ?1", [playlist_id]).map_err(|e| e.to_string())?;
+const EPG_INSERT_BATCH: usize = 5_000;
+// source_key of programmes stored before guides were keyed by source
+const EPG_LEGACY_SOURCE_KEY: &str = "legacy";
+const GUIDE_IMPORT_ABORTED: &str = "Guide import aborted";
 
-    { 
-        let mut cat_stmt = tx.prepare("INSERT INTO categories (playlist_id, name, is_hidden) VALUES (?1, ?2, false)").map_err(|e| e.to_string())?;
-        for cat in &categories {
-            cat_stmt.execute(rusqlite::params![playlist_id, cat.category_name]).map_err(|e| e.to_string())?;
-        }
-    } 
+// Messages from the download side to the guide importer
+enum GuideChunk {
+    Data(Vec<u8>),
+    // Sent once the whole body has arrived
+    End,
+}
 
-    let mut inserted_count = 0;
-    {
-        let mut chan_stmt = tx.prepare(
-            "INSERT OR REPLACE INTO channels (playlist_id, id, name, logo_url, stream_url, category_id, category, epg_channel_id, is_favorite, is_hidden, sort_order)
-             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)"
-        ).map_err(|e| e.to_string())?;
-        
-        for (index, stream) in streams.iter().enumerate() {
-            let final_stream_id = match &stream.stream_id {
-                serde_json::Value::Number(n) => n.as_i64().unwrap_or(0),
-                serde_json::Value::String(s) => s.parse::<i64>().unwrap_or(0),
-                _ => 0,
-            };
-            if final_stream_id == 0 { continue; }
-            let cat_id_str = stream.category_id.as_deref().unwrap_or("0");
-            let category_name = categories.iter().find(|c| c.category_id == cat_id_str).map_or("Uncategorized", |c| &c.category_name);
-            let stream_url = format!("{}{}/{}/{}", clean_url, username, password, final_stream_id);
+// Blocking reader over a guide body that is still downloading. A body that stops without
+// End reads as an error, so a cut-off download is never imported as a complete guide.
+struct GuideBodyReader {
+    rx: tokio::sync::mpsc::Receiver<GuideChunk>,
+    chunk: Vec<u8>,
+    pos: usize,
+    ended: bool,
+}
 
-            // Restore state
-            let (is_fav, is_hid) = existing_state.get(&final_stream_id).copied().unwrap_or((false, false));
-            
-            if let Err(e) = chan_stmt.execute(rusqlite::params![
-                playlist_id, final_stream_id, stream.name, stream.stream_icon, stream_url, Option::<i64>::None, category_name, stream.epg_channel_id.as_deref(),
-                is_fav, is_hid, index as i64 
-            ]) {
-                println!("[ERROR] Failed to insert channel {}: {}", stream.name, e);
-                continue; 
-            }
-            inserted_count += 1;
-            if inserted_count % 500 == 0 { println!("[DEBUG] Inserted {}/{} channels...", inserted_count, streams.len()); }
-        }
-    } 
+impl GuideBodyReader {
+    fn new(rx: tokio::sync::mpsc::Receiver<GuideChunk>) -> Self {
+        GuideBodyReader { rx, chunk: Vec::new(), pos: 0, ended: false }
+    }
+}
 
-    // --- NEW: RESTORE HISTORY ---
-    {
-        let mut hist_stmt = tx.prepare("INSERT OR IGNORE INTO recently_watched (channel_id, last_watched) VALUES (?1, ?2)").map_err(|e| e.to_string())?;
-        for (chan_id, ts) in recent_history {
-            // If the channel was removed by the provider, this insert will be ignored (due to FK violation? 
-            // No, OR IGNORE handles uniqueness, but FK might still error if not careful. 
-            // Actually, sqlite ON DELETE CASCADE removes rows, but here we inserted new channels.
-            // If chan_id exists in channels table, this succeeds. If not, it fails FK constraint.
-            // To be perfectly safe, we just let it try.
-            let _ = hist_stmt.execute(rusqlite::params![chan_id, ts]); 
+impl std::io::Read for GuideBodyReader {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        while self.pos >= self.chunk.len() {
+            if self.ended { return Ok(0); }
+            match self.rx.blocking_recv() {
+                Some(GuideChunk::Data(chunk)) => { self.chunk = chunk; self.pos = 0; },
+                Some(GuideChunk::End) => self.ended = true,
+                None => return Err(std::io::Error::other(GUIDE_IMPORT_ABORTED)),
+            }
         }
+        let n = buf.len().min(self.chunk.len() - self.pos);
+        buf[..n].copy_from_slice(&self.chunk[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
     }
-
-    tx.commit().map_err(|e| e.to_string())?;
-    update_playlist_status(&conn, playlist_id, "active", None)?;
-    let _ = app.emit("playlist-update", get_playlist_by_id(&conn, playlist_id).unwrap_or_default());
-    println!("[DEBUG] SUCCESS! Playlist refresh complete. Total Time: {:.2?}", start_time.elapsed());
-    Ok(())
 }
 
-#[tauri::command]
-fn toggle_category_visibility(id: i64, app: tauri::AppHandle) -> Result<Category, String> {
-    let conn = get_db_connection(&app)?;
-    let is_hidden: bool = conn.query_row("SELECT is_hidden FROM categories WHERE id = ?1", [id], |row| row.get(0)).map_err(|e| e.to_string())?;
-    conn.execute("UPDATE categories SET is_hidden = ?1 WHERE id = ?2", rusqlite::params![!is_hidden, id]).map_err(|e| e.to_string())?;
-    let updated_category = conn.query_row("SELECT * FROM categories WHERE id = ?1", rusqlite::params![id], map_row_to_category).map_err(|e| e.to_string())?;
-    Ok(updated_category)
+// Pull parser over an XMLTV document. Channels and programmes come out one at a time in
+// document order, which puts the <channel> definitions first in a well-formed guide.
+struct GuideParser<R: std::io::BufRead> {
+    reader: quick_xml::Reader<R>,
+    buf: Vec<u8>,
+    state: GuideState,
 }
 
-#[tauri::command]
-fn batch_update_category_visibility(ids: Vec<i64>, is_hidden: bool, app: tauri::AppHandle) -> Result<Vec<Category>, String> {
-    let conn = get_db_connection(&app)?;
-    if ids.is_empty() { return Ok(vec![]); }
-    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let sql = format!("UPDATE categories SET is_hidden = ?1 WHERE id IN ({})", placeholders);
-    let mut params: Vec<rusqlite::types::Value> = vec![is_hidden.into()];
-    params.extend(ids.iter().map(|&id| id.into()));
-    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
-    let fetch_sql = format!("SELECT * FROM categories WHERE id IN ({})", placeholders);
-    let select_params: Vec<rusqlite::types::Value> = ids.iter().map(|&id| id.into()).collect();
-    let mut stmt = conn.prepare(&fetch_sql).map_err(|e| e.to_string())?;
-    let categories = stmt.query_map(rusqlite::params_from_iter(select_params), map_row_to_category).map_err(|e| e.to_string())?.collect::<Result<Vec<Category>, _>>().map_err(|e| e.to_string())?;
-    Ok(categories)
+// What the parser has read of the current <channel> or <programme>
+#[derive(Default)]
+struct GuideState {
+    current: Option<GuideEvent>,
+    field: Option<GuideField>,
+    context: Option<GuideContext>,
+    text: String,
+    // Role and character of the credit being read
+    credit: Option<(String, Option<String>)>,
+    // system attribute of the episode-num being read
+    episode_system: Option<String>,
+    seen_root: bool,
 }
 
-fn fetch_single_channel(conn: &rusqlite::Connection, id: i64) -> Result<Channel, String> {
-    let mut stmt = conn.prepare("SELECT id, playlist_id, name, logo_url, stream_url, category, category_id, is_favorite, is_hidden FROM channels WHERE id = ?1").map_err(|e| e.to_string())?;
-    let channel_tuple = stmt.query_row([id], |row| {
-        Ok((
-            row.get::<_, i64>(0)?, row.get::<_, i64>(1)?, row.get::<_, String>(2)?, row.get::<_, String>(3)?, row.get::<_, String>(4)?,
-            row.get::<_, String>(5)?, row.get::<_, Option<i64>>(6)?, row.get::<_, bool>(7)?, row.get::<_, bool>(8)?,
-        ))
-    }).map_err(|e| e.to_string())?;
+fn guide_attr(element: &quick_xml::events::BytesStart, name: &str) -> Option<String> {
+    element.try_get_attribute(name).ok().flatten()
+        .and_then(|attr| attr.unescape_value().ok().map(|value| value.into_owned()))
+}
 
-    let mut epg_final = Vec::new();
-    let mut epg_stmt_safe = conn.prepare("SELECT id, title, description, start_time, end_time FROM epg_entries WHERE channel_id = ?1").map_err(|e| e.to_string())?;
-    let rows = epg_stmt_safe.query_map([id], |row| {
-        Ok(EpgEntry { id: row.get(0)?, title: row.get(1)?, description: row.get(2)?, start_time: row.get(3)?, end_time: row.get(4)? })
-    }).map_err(|e| e.to_string())?;
-    for r in rows { if let Ok(entry) = r { epg_final.push(entry); } }
+impl<R: std::io::BufRead> GuideParser<R> {
+    fn new(input: R) -> Self {
+        GuideParser { reader: quick_xml::Reader::from_reader(input), buf: Vec::with_capacity(4096), state: GuideState::default() }
+    }
 
-    Ok(Channel {
-        id: channel_tuple.0, playlist_id: channel_tuple.1, name: channel_tuple.2, logo_url: channel_tuple.3, stream_url: channel_tuple.4,
-        epg: epg_final, category: channel_tuple.5, category_id: channel_tuple.6, is_favorite: channel_tuple.7, is_hidden: channel_tuple.8,
-    })
+    fn next_event(&mut self) -> Result<Option<GuideEvent>, String> {
+        use quick_xml::events::Event;
+        loop {
+            self.buf.clear();
+            let event = self.reader.read_event_into(&mut self.buf)
+                .map_err(|e| ErrorKind::Parse.error(format!("XMLTV at byte {}: {}", self.reader.buffer_position(), e)))?;
+            let item = match event {
+                Event::Start(e) => { self.state.start(&e); None },
+                Event::Empty(e) => self.state.empty(&e),
+                Event::Text(t) if self.state.field.is_some() => {
+                    let text = t.unescape().map_err(|e| ErrorKind::Parse.error(format!("XMLTV: {}", e)))?;
+                    self.state.text.push_str(&text);
+                    None
+                },
+                Event::CData(t) if self.state.field.is_some() => { self.state.text.push_str(&String::from_utf8_lossy(&t)); None },
+                Event::End(e) => self.state.end(e.local_name().as_ref()),
+                Event::Eof if !self.state.seen_root => return Err(ErrorKind::Parse.error("XMLTV: no <tv> element").into()),
+                Event::Eof => return Ok(None),
+                _ => None,
+            };
+            if item.is_some() { return Ok(item); }
+        }
+    }
 }
 
-#[tauri::command]
-fn toggle_channel_favorite(id: i64, app: tauri::AppHandle) -> Result<Channel, String> {
-    let conn = get_db_connection(&app)?;
-    conn.execute("UPDATE channels SET is_favorite = NOT is_favorite WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
-    fetch_single_channel(&conn, id)
+impl GuideState {
+    fn programme(&mut self) -> Option<&mut GuideProgramme> {
+        match self.current.as_mut() {
+            Some(GuideEvent::Programme(programme)) => Some(programme),
+            _ => None,
+        }
+    }
+
+    fn start(&mut self, e: &quick_xml::events::BytesStart) {
+        // Fields are text-only; markup inside one (an actor's <image>, ...) ends it
+        if self.field.is_some() { self.end_field(); }
+        let name = e.local_name();
+        match name.as_ref() {
+            // Entries missing their key attributes are skipped rather than failing the guide
+            b"channel" => self.current = guide_attr(e, "id").map(|id| GuideEvent::Channel { id, display_name: None }),
+            b"programme" => self.current = match (guide_attr(e, "start"), guide_attr(e, "stop"), guide_attr(e, "channel")) {
+                (Some(start), Some(stop), Some(channel)) => Some(GuideEvent::Programme(Box::new(GuideProgramme { start, stop, channel, ..Default::default() }))),
+                _ => None,
+            },
+            b"display-name" => self.begin_field(GuideField::DisplayName),
+            b"title" => self.begin_field(GuideField::Title),
+            b"sub-title" => self.begin_field(GuideField::SubTitle),
+            b"desc" => self.begin_field(GuideField::Desc),
+            b"category" => self.begin_field(GuideField::Category),
+            b"date" => self.begin_field(GuideField::Date),
+            b"episode-num" => {
+                self.episode_system = guide_attr(e, "system");
+                self.begin_field(GuideField::EpisodeNum);
+            },
+            b"rating" => self.context = Some(GuideContext::Rating),
+            b"star-rating" => self.context = Some(GuideContext::StarRating),
+            b"credits" => self.context = Some(GuideContext::Credits),
+            b"value" => match self.context {
+                Some(GuideContext::Rating) => self.begin_field(GuideField::Rating),
+                Some(GuideContext::StarRating) => self.begin_field(GuideField::StarRating),
+                _ => {},
+            },
+            role if self.context == Some(GuideContext::Credits) => {
+                let role = String::from_utf8_lossy(role).into_owned();
+                if XMLTV_CREDIT_ROLES.contains(&role.as_str()) {
+                    self.credit = Some((role, guide_attr(e, "role")));
+                    self.begin_field(GuideField::Credit);
+                }
+            },
+            _ => self.mark(e),
+        }
+    }
+
+    fn empty(&mut self, e: &quick_xml::events::BytesStart) -> Option<GuideEvent> {
+        match e.local_name().as_ref() {
+            b"channel" => guide_attr(e, "id").map(|id| GuideEvent::Channel { id, display_name: None }),
+            _ => { self.mark(e); None },
+        }
+    }
+
+    // Elements that count by their presence or attributes, empty or not
+    fn mark(&mut self, e: &quick_xml::events::BytesStart) {
+        let name = e.local_name();
+        if name.as_ref() == b"tv" { self.seen_root = true; }
+        // Icons inside <rating> or <star-rating> belong to the rating, not the programme
+        let in_context = self.context.is_some();
+        let Some(programme) = self.programme() else { return };
+        let details = &mut programme.details;
+        match name.as_ref() {
+            b"icon" if !in_context && details.icon.is_none() => details.icon = guide_attr(e, "src"),
+            b"new" => details.is_new = true,
+            b"premiere" => details.is_premiere = true,
+            b"previously-shown" => details.is_previously_shown = true,
+            _ => {},
+        }
+    }
+
+    fn end(&mut self, name: &[u8]) -> Option<GuideEvent> {
+        match name {
+            b"channel" | b"programme" => {
+                self.context = None;
+                match self.current.take() {
+                    // A programme without a title has nothing to show
+                    Some(GuideEvent::Programme(programme)) if programme.title.is_empty() => None,
+                    item => item,
+                }
+            },
+            b"rating" | b"star-rating" | b"credits" => { self.context = None; None },
+            _ => { self.end_field(); None },
+        }
+    }
+
+    fn begin_field(&mut self, field: GuideField) {
+        if self.current.is_none() { return; }
+        self.field = Some(field);
+        self.text.clear();
+    }
+
+    fn end_field(&mut self) {
+        let Some(field) = self.field.take() else { return };
+        let text = self.text.trim().to_string();
+        if text.is_empty() { return; }
+        if let Some(GuideEvent::Channel { display_name, .. }) = self.current.as_mut() {
+            if field == GuideField::DisplayName && display_name.is_none() { *display_name = Some(text); }
+            return;
+        }
+        let (credit, episode_system) = (self.credit.take(), self.episode_system.take());
+        let Some(programme) = self.programme() else { return };
+        let details = &mut programme.details;
+        match field {
+            GuideField::Title if programme.title.is_empty() => programme.title = text,
+            GuideField::Desc if programme.desc.is_none() => programme.desc = Some(text),
+            GuideField::SubTitle if details.sub_title.is_none() => details.sub_title = Some(text),
+            GuideField::Category if !details.categories.contains(&text) => details.categories.push(text),
+            GuideField::Date if details.date.is_none() => details.date = Some(text),
+            GuideField::Rating if details.rating.is_none() => details.rating = Some(text),
+            GuideField::StarRating if details.star_rating.is_none() => details.star_rating = Some(text),
+            GuideField::EpisodeNum => match episode_system.as_deref() {
+                Some("xmltv_ns") if details.episode_num.is_none() => details.episode_num = Some(text),
+                Some("onscreen") if details.episode_onscreen.is_none() => details.episode_onscreen = Some(text),
+                _ => {},
+            },
+            GuideField::Credit => if let Some((role, character)) = credit {
+                details.credits.push(ProgrammeCredit { role, name: text, character });
+            },
+            _ => {},
+        }
+    }
 }
 
-#[tauri::command]
-fn toggle_channel_visibility(id: i64, app: tauri::AppHandle) -> Result<Channel, String> {
-    let conn = get_db_connection(&app)?;
-    conn.execute("UPDATE channels SET is_hidden = NOT is_hidden WHERE id = ?1", [id]).map_err(|e| e.to_string())?;
-    fetch_single_channel(&conn, id)
-}
+// Guide channel key (EPG id or sanitized name) -> channels it fills
+type GuideChannelMap = HashMap<String, Vec<i64>>;
 
-#[tauri::command]
-fn batch_update_channel_visibility(ids: Vec<i64>, is_hidden: bool, app: tauri::AppHandle) -> Result<Vec<Channel>, String> {
-    let conn = get_db_connection(&app)?;
-    if ids.is_empty() { return Ok(vec![]); }
-    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let sql = format!("UPDATE channels SET is_hidden = ?1 WHERE id IN ({})", placeholders);
-    let mut params: Vec<rusqlite::types::Value> = vec![is_hidden.into()];
-    params.extend(ids.iter().map(|&id| id.into()));
-    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
-    Ok(vec![]) 
+// The channels a guide may fill, and their own time shifts
+#[derive(Default)]
+struct GuideTargets {
+    by_epg_id: GuideChannelMap,
+    by_name: GuideChannelMap,
+    // Channel id -> minutes, only for channels with a non-zero shift
+    shifts: HashMap<i64, i64>,
 }
 
-#[tauri::command]
-fn batch_update_channel_favorite_status(ids: Vec<i64>, is_favorite: bool, app: tauri::AppHandle) -> Result<Vec<Channel>, String> {
-    let conn = get_db_connection(&app)?;
-    if ids.is_empty() { return Ok(vec![]); }
-    let placeholders = ids.iter().map(|_| "?").collect::<Vec<_>>().join(",");
-    let sql = format!("UPDATE channels SET is_favorite = ?1 WHERE id IN ({})", placeholders);
-    let mut params: Vec<rusqlite::types::Value> = vec![is_favorite.into()];
-    params.extend(ids.iter().map(|&id| id.into()));
-    conn.execute(&sql, rusqlite::params_from_iter(params)).map_err(|e| e.to_string())?;
-    Ok(vec![])
+impl GuideTargets {
+    fn is_empty(&self) -> bool {
+        self.by_epg_id.is_empty() && self.by_name.is_empty()
+    }
 }
 
-#[tauri::command]
-fn add_to_recently_watched(channel_id: i64, app: tauri::AppHandle) -> Result<(), String> {
-    let conn = get_db_connection(&app)?;
-    let now = chrono::Utc::now().to_rfc3339();
-    
-    // 1. Upsert the record (Insert or Update timestamp)
-    conn.execute(
-        "INSERT OR REPLACE INTO recently_watched (channel_id, last_watched) VALUES (?1, ?2)",
-        rusqlite::params![channel_id, now],
-    ).map_err(|e| e.to_string())?;
+// Channels a guide may fill, keyed by EPG id and by sanitized name. Channels already
+// covered by a higher-priority guide are left out.
+fn build_guide_maps(conn: &rusqlite::Connection, scope: Option<i64>, covered: &HashSet<i64>) -> Result<GuideTargets, String> {
+    // --- CHANGED: Maps now hold a VECTOR of IDs (One-to-Many) ---
+    // 1. Build MAP 1: API ID -> List of DB IDs
+    // 2. Build MAP 2: Sanitized Name -> List of DB IDs
+    let mut targets = GuideTargets::default();
 
-    // 2. Prune: Keep only top 15
-    conn.execute(
-        "DELETE FROM recently_watched WHERE channel_id NOT IN (
-            SELECT channel_id FROM recently_watched ORDER BY last_watched DESC LIMIT 15
-        )",
-        [],
+    let mut stmt = conn.prepare(
+        "SELECT c.id, c.epg_channel_id, c.name, c.epg_shift FROM channels c JOIN playlists p ON p.id = c.playlist_id
+         WHERE p.is_active = true AND (?1 IS NULL OR c.playlist_id = ?1)"
     ).map_err(|e| e.to_string())?;
+    let rows = stmt.query_map([scope], |row| {
+        let chan_id: i64 = row.get(0)?;
+        let epg_id: Option<String> = row.get(1)?;
+        let name: String = row.get(2)?;
+        let shift: i64 = row.get(3)?;
+        Ok((chan_id, epg_id, name, shift))
+    }).map_err(|e| e.to_string())?;
 
-    Ok(())
+    for (chan_id, epg_id, name, shift) in rows.flatten() {
+        if covered.contains(&chan_id) { continue; }
+        if let Some(eid) = epg_id.filter(|e| !e.is_empty()) {
+            targets.by_epg_id.entry(eid).or_default().push(chan_id);
+        }
+        targets.by_name.entry(sanitize_name(&name)).or_default().push(chan_id);
+        if shift != 0 { targets.shifts.insert(chan_id, shift); }
+    }
+    Ok(targets)
 }
-#[tauri::command]
-#[allow(unused_variables)]
-fn toggle_vod_watchlist(id: Value, r#type: Value) -> Result<Value, String> { Ok(json!(null)) }
-#[tauri::command]
-#[allow(unused_variables)]
-fn toggle_vod_favorite(id: Value, r#type: Value) -> Result<Value, String> { Ok(json!(null)) }
-
-#[tauri::command]
-fn play_stream(url: String, app: tauri::AppHandle) -> Result<(), String> {
-    use tauri_plugin_shell::ShellExt;
-    
-    println!("[Player] Attempting to play: {}", url);
 
-    // "mpv" matches the binary name, NOT the path in tauri.conf.json
-    let _child = app.shell().sidecar("mpv")
-        .map_err(|e| format!("Failed to find sidecar: {}", e))? 
-        .args([
-            "--force-window",   // Open window immediately (don't wait for buffer)
-            "--geometry=60%",   // Sets window width to 60% of screen (maintains aspect ratio)
-            &url                // The stream URL
-        ])
-        .spawn()
-        .map_err(|e| format!("Failed to launch MPV: {}", e))?;
+// Programme columns shared by epg_entries and the staging table an import fills
+const EPG_STAGING_COLUMNS: &str = "channel_id, title, description, start_time, end_time, sub_title, categories, icon,
+    episode_num, episode_onscreen, rating, star_rating, credits, date, is_new, is_premiere, is_previously_shown";
 
-    Ok(())
+// Writes the programmes of one guide into the connection's staging table, one short
+// transaction per batch. The staging table is TEMP, so these writes never lock the database.
+struct GuideWriter<'a> {
+    conn: &'a rusqlite::Connection,
+    timing: GuideTiming,
+    shifts: &'a HashMap<i64, i64>,
+    filled: HashSet<i64>,
+    // Rows the guide lists more than once for a channel (same start and title); only the first is kept
+    duplicates: usize,
 }
 
-fn convert_to_iso(xmltv_date: &str) -> String {
-    // Helper to force UTC
-    let to_utc = |dt: chrono::DateTime<chrono::FixedOffset>| -> String {
-        dt.with_timezone(&chrono::Utc).to_rfc3339()
-    };
+impl GuideWriter<'_> {
+    // Stages a batch of matched programmes, once for each channel they fill, with the
+    // guide's offset plus each channel's own shift applied. Programmes outside the retention
+    // window are skipped. Returns the number of rows written.
+    fn write(&mut self, batch: &mut Vec<(Box<GuideProgramme>, &[i64])>) -> Result<usize, String> {
+        let timing = self.timing;
+        let mut rows = 0;
+        let tx = self.conn.unchecked_transaction().map_err(|e| e.to_string())?;
+        {
+            let mut stmt = tx.prepare_cached(&format!(
+                "INSERT OR IGNORE INTO temp.epg_staging ({}) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17)",
+                EPG_STAGING_COLUMNS
+            )).map_err(|e| e.to_string())?;
+            for (programme, target_ids) in batch.drain(..) {
+                let start = parse_xmltv_time(&programme.start, timing.timezone);
+                let stop = parse_xmltv_time(&programme.stop, timing.timezone);
+                let details = &programme.details;
+                let categories = (!details.categories.is_empty()).then(|| serde_json::to_string(&details.categories).unwrap_or_default());
+                let credits = (!details.credits.is_empty()).then(|| serde_json::to_string(&details.credits).unwrap_or_default());
 
-    // Try format with space: "20241118120000 +0000"
-    if let Ok(dt) = chrono::DateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S %z") {
-        return to_utc(dt);
-    }
-    // Try format without space: "20241118120000+0000"
-    if let Ok(dt) = chrono::DateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S%z") {
-        return to_utc(dt);
-    }
-    // Fallback: Assume UTC if no timezone provided
-    if let Ok(dt) = chrono::NaiveDateTime::parse_from_str(xmltv_date, "%Y%m%d%H%M%S") {
-        return dt.and_utc().to_rfc3339();
+                // Loop through ALL matched channels (SD, HD, FHD) and insert for each
+                for &internal_channel_id in target_ids {
+                    let shift = chrono::Duration::minutes(timing.offset_minutes + self.shifts.get(&internal_channel_id).copied().unwrap_or(0));
+                    let (start, stop) = (start.map(|t| t + shift), stop.map(|t| t + shift));
+                    if let (Some((from, until)), Some(start), Some(stop)) = (timing.retention, start, stop) {
+                        if stop < from || start > until { continue; }
+                    }
+                    self.filled.insert(internal_channel_id);
+
+                    let written = stmt.execute(rusqlite::params![
+                        internal_channel_id,
+                        programme.title,
+                        programme.desc,
+                        start.map_or_else(|| programme.start.clone(), |t| t.to_rfc3339()),
+                        stop.map_or_else(|| programme.stop.clone(), |t| t.to_rfc3339()),
+                        details.sub_title,
+                        categories,
+                        details.icon,
+                        details.episode_num,
+                        details.episode_onscreen,
+                        details.rating,
+                        details.star_rating,
+                        credits,
+                        details.date,
+                        details.is_new,
+                        details.is_premiere,
+                        details.is_previously_shown
+                    ]).map_err(|e| e.to_string())?;
+                    rows += written;
+                    self.duplicates += 1 - written;
+                }
+            }
+        }
+        tx.commit().map_err(|e| e.to_string())?;
+        Ok(rows)
     }
-    
-    xmltv_date.to_string()
 }
 
-// --- UPDATED: EPG with Waterfall Fallback ---
-#[tauri::command]
-async fn refresh_epg(app: tauri::AppHandle) -> Result<(), String> {
-    let start_time = std::time::Instant::now();
-    println!("[EPG] Starting EPG Refresh...");
+// Outcome of one guide import
+struct GuideImport {
+    // Channels that received programmes
+    filled: HashSet<i64>,
+    programmes: usize,
+    duplicates: usize,
+}
 
-    let mut conn = get_db_connection(&app)?;
-    let playlists_creds = conn.prepare("SELECT id, url, username, password FROM playlists WHERE is_active = true AND type = 'xtream'").map_err(|e| e.to_string())?
-        .query_map([], |row| { Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?, row.get::<_, Option<String>>(2)?, row.get::<_, Option<String>>(3)?)) }).map_err(|e| e.to_string())?
-        .collect::<Result<Vec<(i64, String, Option<String>, Option<String>)>, _>>().map_err(|e| e.to_string())?;
+// Matches a streamed guide against the channel maps and stages its programmes in batches
+// as they are parsed. Once the whole guide has parsed, one short transaction replaces what
+// this guide stored last time (and what other guides stored for the channels it now fills)
+// with the staged rows, so a failed download leaves the previous programmes in place.
+fn import_guide<R: std::io::BufRead>(conn: &rusqlite::Connection, parser: &mut GuideParser<R>, targets: &GuideTargets, source_key: &str, timing: GuideTiming) -> Result<GuideImport, String> {
+    // 3. Build MAP 3: XML ID -> Sanitized Display Name, filled as the <channel> definitions go by
+    let mut xml_alias_map: HashMap<String, String> = HashMap::new();
 
-    let client = Client::new();
-    conn.execute("DELETE FROM epg_entries", []).map_err(|e| e.to_string())?;
+    conn.execute_batch(
+        "CREATE TEMP TABLE IF NOT EXISTS epg_staging (
+            channel_id INTEGER NOT NULL, title TEXT NOT NULL, description TEXT, start_time TEXT NOT NULL, end_time TEXT NOT NULL,
+            sub_title TEXT, categories TEXT, icon TEXT, episode_num TEXT, episode_onscreen TEXT, rating TEXT, star_rating TEXT,
+            credits TEXT, date TEXT, is_new BOOLEAN NOT NULL, is_premiere BOOLEAN NOT NULL, is_previously_shown BOOLEAN NOT NULL,
+            UNIQUE(channel_id, start_time, title)
+        );
+        DELETE FROM temp.epg_staging;
+        CREATE INDEX IF NOT EXISTS temp.idx_epg_staging_channel ON epg_staging(channel_id);"
+    ).map_err(|e| e.to_string())?;
+    let mut match_count = 0;
+    let mut name_match_count = 0;
+    let mut writer = GuideWriter { conn, timing, shifts: &targets.shifts, filled: HashSet::new(), duplicates: 0 };
+    let mut pending: Vec<(Box<GuideProgramme>, &[i64])> = Vec::with_capacity(EPG_INSERT_BATCH);
 
-    for (playlist_id, base_url, username_opt, password_opt) in playlists_creds {
-        let (username, password) = match (username_opt, password_opt) {
-            (Some(u), Some(p)) => (u, p),
-            _ => continue, 
+    while let Some(event) = parser.next_event()? {
+        let programme = match event {
+            GuideEvent::Channel { id, display_name } => {
+                if let Some(name) = display_name { xml_alias_map.insert(id, sanitize_name(&name)); }
+                continue;
+            },
+            GuideEvent::Programme(programme) => programme,
         };
 
-        // --- CHANGED: Maps now hold a VECTOR of IDs (One-to-Many) ---
-        // 1. Build MAP 1: API ID -> List of DB IDs
-        let mut epg_id_map: HashMap<String, Vec<i64>> = HashMap::new();
-        // 2. Build MAP 2: Sanitized Name -> List of DB IDs
-        let mut name_map: HashMap<String, Vec<i64>> = HashMap::new();
+        // --- WATERFALL MATCHING STRATEGY (MULTI-TARGET) ---
+        
+        // 1. Try finding targets by ID
+        let targets_by_id = targets.by_epg_id.get(&programme.channel);
+        
+        // 2. Try finding targets by Name
+        let targets_by_name = if let Some(sanitized_xml_name) = xml_alias_map.get(&programme.channel) {
+            targets.by_name.get(sanitized_xml_name)
+        } else { None };
 
-        { 
-            let mut stmt = conn.prepare("SELECT id, epg_channel_id, name FROM channels WHERE playlist_id = ?1").map_err(|e| e.to_string())?;
-            let rows = stmt.query_map([playlist_id], |row| {
-                let chan_id: i64 = row.get(0)?;
-                let epg_id: Option<String> = row.get(1)?;
-                let name: String = row.get(2)?;
-                Ok((chan_id, epg_id, name))
-            }).map_err(|e| e.to_string())?;
+        // 3. Combine them (Prioritize ID, fallback to Name)
+        let final_targets = match (targets_by_id, targets_by_name) {
+            (Some(ids), _) => Some(ids), // ID Match takes precedence
+            (None, Some(names)) => {
+                name_match_count += 1;
+                Some(names) // Fallback to Name match
+            },
+            (None, None) => None
+        };
 
-            for row in rows {
-                if let Ok((chan_id, epg_id, name)) = row {
-                    if let Some(eid) = epg_id { 
-                        epg_id_map.entry(eid).or_default().push(chan_id); 
-                    }
-                    name_map.entry(sanitize_name(&name)).or_default().push(chan_id);
-                }
+        if let Some(target_ids) = final_targets {
+            pending.push((programme, target_ids.as_slice()));
+            if pending.len() >= EPG_INSERT_BATCH {
+                match_count += writer.write(&mut pending)?;
             }
-        } 
+        }
+    }
+    match_count += writer.write(&mut pending)?;
+    let GuideWriter { filled, duplicates, .. } = writer;
 
-        println!("[EPG] Playlist {}: Mapped IDs for {} distinct EPG codes and {} distinct names.", playlist_id, epg_id_map.len(), name_map.len());
+    // --- SWAP THE STAGED GUIDE IN ---
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM epg_entries WHERE source_key = ?1", [source_key]).map_err(|e| e.to_string())?;
+    tx.execute(
+        "DELETE FROM epg_entries WHERE source_key != ?1 AND channel_id IN (SELECT DISTINCT channel_id FROM temp.epg_staging)",
+        [source_key],
+    ).map_err(|e| e.to_string())?;
+    tx.execute(
+        &format!("INSERT INTO epg_entries ({0}, source_key) SELECT {0}, ?1 FROM temp.epg_staging", EPG_STAGING_COLUMNS),
+        [source_key],
+    ).map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    conn.execute("DELETE FROM temp.epg_staging", []).map_err(|e| e.to_string())?;
 
-        let mut clean_url = base_url.trim().to_string();
-        if clean_url.ends_with("player_api.php") { clean_url = clean_url.replace("player_api.php", ""); }
-        if !clean_url.ends_with('/') { clean_url.push('/'); }
-        let epg_url = format!("{}xmltv.php?username={}&password={}", clean_url, username, password);
-        println!("[EPG] Fetching XML: {}", epg_url);
+    println!("[EPG] Matched and inserted {} programs ({} via Name Fallback).", match_count, name_match_count);
+    Ok(GuideImport { filled, programmes: match_count, duplicates })
+}
 
-        let epg_xml_bytes = match client.get(&epg_url).send().await {
-            Ok(resp) => resp.bytes().await.map_err(|e| e.to_string())?,
-            Err(e) => { println!("[EPG] Network Error: {}", e); continue; }, 
-        };
+// Hands a guide download to the importer chunk by chunk. Returns the bytes downloaded.
+async fn stream_guide_body(mut resp: reqwest::Response, tx: tokio::sync::mpsc::Sender<GuideChunk>) -> Result<usize, String> {
+    let mut downloaded = 0;
+    while let Some(chunk) = resp.chunk().await.map_err(request_error)? {
+        downloaded += chunk.len();
+        tx.send(GuideChunk::Data(chunk.to_vec())).await.map_err(|_| "Guide importer stopped".to_string())?;
+    }
+    tx.send(GuideChunk::End).await.map_err(|_| "Guide importer stopped".to_string())?;
+    Ok(downloaded)
+}
 
-        let tv_data: Tv = match from_reader(epg_xml_bytes.as_ref()) {
-            Ok(data) => data,
-            Err(e) => { println!("[EPG] XML Parse Error: {}", e); continue; }, 
+// Imports one guide: a blocking importer decompresses, parses and inserts the guide while
+// it is still downloading, so memory stays flat however big the guide is
+async fn import_guide_job(app: &tauri::AppHandle, job: &GuideJob, targets: GuideTargets) -> Result<GuideImport, String> {
+    let (tx, rx) = tokio::sync::mpsc::channel::<GuideChunk>(8);
+    let local_path = local_guide_path(job);
+    let is_local = local_path.is_some();
+    let importer_app = app.clone();
+    let timing = job.timing;
+    let source_key = job.key.clone();
+    let importer = tokio::task::spawn_blocking(move || -> Result<GuideImport, String> {
+        let input: Box<dyn std::io::Read + Send> = match local_path {
+            Some(path) => Box::new(std::fs::File::open(&path).map_err(|e| format!("Cannot open {}: {}", path, e))?),
+            None => Box::new(GuideBodyReader::new(rx)),
         };
+        let mut parser = GuideParser::new(open_guide(input)?);
+        let conn = get_db_connection(&importer_app)?;
+        import_guide(&conn, &mut parser, &targets, &source_key, timing)
+    });
 
-        // 3. Build MAP 3: XML ID -> Sanitized Display Name
-        let mut xml_alias_map: HashMap<String, String> = HashMap::new();
-        for chan_def in tv_data.channels {
-            if let Some(first_name) = chan_def.display_names.first() {
-                xml_alias_map.insert(chan_def.id, sanitize_name(&first_name.value));
-            }
+    let streamed = if is_local {
+        Ok(())
+    } else {
+        match open_remote_guide(app, job).await {
+            Ok(resp) => stream_guide_body(resp, tx).await.map(|_| ()),
+            // Dropping the sender lets the importer see the body as aborted
+            Err(e) => { drop(tx); Err(e) },
         }
+    };
+    let imported = importer.await.map_err(|e| e.to_string())?;
+    match (streamed, imported) {
+        (Ok(()), imported) => imported,
+        // A failing importer shows up on the download side as a closed channel
+        (Err(_), Err(e)) if !e.contains(GUIDE_IMPORT_ABORTED) => Err(e),
+        (Err(e), _) => Err(e),
+    }
+}
 
-        let tx = conn.transaction().map_err(|e| e.to_string())?;
-        let mut match_count = 0;
-        let mut name_match_count = 0;
+// Drops programmes outside the retention window and those of guides that are no longer
+// refreshed (deleted or disabled sources, removed playlists). Legacy rows are left to the
+// guides that replace them.
+fn prune_guide(conn: &rusqlite::Connection, jobs: &[GuideJob]) -> Result<(), String> {
+    if let Some((from, until)) = jobs.first().and_then(|job| job.timing.retention) {
+        conn.execute(
+            "DELETE FROM epg_entries WHERE end_time < ?1 OR start_time > ?2",
+            [from.to_rfc3339(), until.to_rfc3339()],
+        ).map_err(|e| e.to_string())?;
+    }
+    let placeholders = jobs.iter().map(|_| "?").chain(std::iter::once("?")).collect::<Vec<_>>().join(",");
+    let sql = format!("DELETE FROM epg_entries WHERE source_key NOT IN ({})", placeholders);
+    let keep = jobs.iter().map(|job| job.key.as_str()).chain(std::iter::once(EPG_LEGACY_SOURCE_KEY));
+    conn.execute(&sql, rusqlite::params_from_iter(keep)).map_err(|e| e.to_string())?;
+    Ok(())
+}
 
-        {
-            let mut epg_stmt = tx.prepare(
-                "INSERT OR IGNORE INTO epg_entries (id, channel_id, title, description, start_time, end_time)
-                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)"
-            ).map_err(|e| e.to_string())?;
+// --- UPDATED: EPG with Waterfall Fallback ---
+// Imports every guide in priority order: user sources, then the Xtream providers' own guides
+#[tauri::command]
+async fn refresh_epg(app: tauri::AppHandle) -> Result<(), String> {
+    let start_time = std::time::Instant::now();
+    println!("[EPG] Starting EPG Refresh...");
 
-            for programme in tv_data.programmes {
-                // --- WATERFALL MATCHING STRATEGY (MULTI-TARGET) ---
-                
-                // 1. Try finding targets by ID
-                let targets_by_id = epg_id_map.get(&programme.channel);
-                
-                // 2. Try finding targets by Name
-                let targets_by_name = if let Some(sanitized_xml_name) = xml_alias_map.get(&programme.channel) {
-                    name_map.get(sanitized_xml_name)
-                } else { None };
-
-                // 3. Combine them (Prioritize ID, fallback to Name)
-                let final_targets = match (targets_by_id, targets_by_name) {
-                    (Some(ids), _) => Some(ids), // ID Match takes precedence
-                    (None, Some(names)) => {
-                        name_match_count += 1;
-                        Some(names) // Fallback to Name match
-                    },
-                    (None, None) => None
-                };
-
-                if let Some(target_ids) = final_targets {
-                    let iso_start = convert_to_iso(&programme.start);
-                    let iso_stop = convert_to_iso(&programme.stop);
-
-                    // Loop through ALL matched channels (SD, HD, FHD) and insert for each
-                    for &internal_channel_id in target_ids {
-                        match_count += 1;
-                        // We need a unique ID for the entry row, but it must be unique per channel_id
-                        let entry_id = format!("{}-{}-{}", internal_channel_id, programme.start, programme.title.value);
-                        let entry_hash_id = i64::from(crc32fast::hash(entry_id.as_bytes()));
-                        
-                        epg_stmt.execute(rusqlite::params![
-                            entry_hash_id,
-                            internal_channel_id,
-                            programme.title.value,
-                            programme.desc.as_ref().map(|d| &d.value), // Fixed .as_ref() borrow issue
-                            iso_start, 
-                            iso_stop   
-                        ]).map_err(|e| e.to_string())?;
-                    }
-                }
-            }
+    let conn = get_db_connection(&app)?;
+    let jobs = collect_guide_jobs(&conn)?;
+    prune_guide(&conn, &jobs)?;
+    let mut covered: HashSet<i64> = HashSet::new();
+
+    for job in jobs {
+        let targets = build_guide_maps(&conn, job.scope, &covered)?;
+        println!("[EPG] {}: Mapped IDs for {} distinct EPG codes and {} distinct names.", job.label, targets.by_epg_id.len(), targets.by_name.len());
+        if targets.is_empty() {
+            // Every channel it could fill is covered by a guide that goes first
+            conn.execute("DELETE FROM epg_entries WHERE source_key = ?1", [&job.key]).map_err(|e| e.to_string())?;
+            continue;
+        }
+
+        let imported = import_guide_job(&app, &job, targets).await;
+        if let GuideOrigin::Source(source) = &job.origin {
+            set_epg_source_status(&conn, source.id, imported.as_ref().err().map(|e| e.as_str()))?;
+        }
+        match imported {
+            Ok(imported) => {
+                println!("[EPG] {}: {} programmes for {} channels ({} duplicates skipped).", job.label, imported.programmes, imported.filled.len(), imported.duplicates);
+                covered.extend(imported.filled);
+            },
+            Err(e) => {
+                println!("[EPG] {} failed, keeping its previous programmes: {}", job.label, e);
+                // Guides further down must not fill the channels that still show this one
+                let mut stmt = conn.prepare("SELECT DISTINCT channel_id FROM epg_entries WHERE source_key = ?1").map_err(|e| e.to_string())?;
+                let kept = stmt.query_map([&job.key], |row| row.get::<_, i64>(0)).map_err(|e| e.to_string())?;
+                covered.extend(kept.flatten());
+            },
         }
-        tx.commit().map_err(|e| e.to_string())?;
-        println!("[EPG] Matched and inserted {} programs ({} via Name Fallback).", match_count, name_match_count);
     }
     
     println!("[EPG] Refresh Complete. Time: {:.2?}", start_time.elapsed());
@@ -1094,6 +4596,7 @@ async fn refresh_epg(app: tauri::AppHandle) -> Result<(), String> {
 fn main() {
     tauri::Builder::default()
     .plugin(tauri_plugin_shell::init()) 
+    .manage(RefreshRegistry::default())
     .invoke_handler(tauri::generate_handler![
         initialize_database,
         get_playlists,
@@ -1103,6 +4606,7 @@ fn main() {
         refresh_all_playlists,
         get_channels,
         get_movies,
+        get_vod_categories,
         get_series,
         get_seasons_for_series,
         get_categories,
@@ -1110,7 +4614,11 @@ fn main() {
         update_playlist,
         delete_playlist,
         update_playlist_active_status,
+        set_playlist_output_format,
         refresh_playlist,
+        cancel_refresh,
+        cancel_all_refreshes,
+        test_playlist_connection,
         toggle_category_visibility,
         batch_update_category_visibility,
         toggle_channel_favorite,
@@ -1118,11 +4626,648 @@ fn main() {
         toggle_vod_watchlist,
         toggle_vod_favorite,
         toggle_channel_visibility,
+        set_channel_epg_shift,
         batch_update_channel_visibility,
         batch_update_channel_favorite_status,
         play_stream,
-        refresh_epg
+        tune_channel_by_number,
+        get_catchup_programmes,
+        play_catchup,
+        refresh_epg,
+        get_epg_sources,
+        add_epg_source,
+        update_epg_source,
+        delete_epg_source
     ])
     .run(tauri::generate_context!())
     .expect("error while running tauri application");
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Synthetic `get_live_streams` body shaped like a large provider's: string and numeric ids
+    // mixed, escaped characters in names and the usual unused fields
+    fn synthetic_live_streams_fixture(stream_count: usize, category_count: usize) -> Vec<u8> {
+        let mut body = String::with_capacity(stream_count * 320);
+        body.push('[');
+        for i in 0..stream_count {
+            if i > 0 { body.push(','); }
+            let stream_id = if i % 2 == 0 { format!("{}", 100_000 + i) } else { format!("\"{}\"", 100_000 + i) };
+            body.push_str(&format!(
+                "{{\"num\":{},\"name\":\"UK: Channel {} \\\"HD\\\" [{}]\",\"stream_type\":\"live\",\"stream_id\":{},\"stream_icon\":\"http:\\/\\/logos.example.com\\/{}.png\",\"epg_channel_id\":\"channel{}.uk\",\"added\":\"1700000000\",\"category_id\":\"{}\",\"custom_sid\":\"\",\"tv_archive\":{},\"direct_source\":\"\",\"tv_archive_duration\":\"{}\"}}",
+                i + 1, i, i % 7, stream_id, i, i, i % category_count.max(1), i % 3 % 2, if i % 3 == 1 { 7 } else { 0 }
+            ));
+        }
+        body.push(']');
+        body.into_bytes()
+    }
+
+    // Feeds a fixture through the same decoder, staging table and writer a refresh uses, timing
+    // the download side (decode + stage) and the import separately
+    fn run_live_import_benchmark(conn: &rusqlite::Connection, fixture: &[u8], categories: &[XtreamCategory]) -> Result<(std::time::Duration, std::time::Duration, usize), String> {
+        let account = XtreamAccount { base_url: "http://bench.invalid/".to_string(), username: "user".to_string(), password: "pass".to_string() };
+        let live_categories: Vec<LiveCategory> = categories.iter()
+            .map(|c| LiveCategory { remote_id: c.category_id.clone(), name: c.category_name.clone() })
+            .collect();
+        let mut decoder = XtreamLiveDecoder::new(account, None, categories);
+        let task = RefreshTask::untracked();
+
+        let started = std::time::Instant::now();
+        begin_live_staging(conn)?;
+        let mut count = 0;
+        // Roughly the chunk size an HTTP body arrives in
+        for chunk in fixture.chunks(16 * 1024) {
+            let entries = decoder.feed(chunk)?;
+            stage_live_entries(conn, &entries)?;
+            count += entries.len();
+        }
+        decoder.finish()?;
+        let download = started.elapsed();
+
+        let started = std::time::Instant::now();
+        save_staged_live_channels(conn, &task, 1, &live_categories)?;
+        Ok((download, started.elapsed(), count))
+    }
+
+    // Imports a synthetic provider response (100k streams) into an in-memory database to keep
+    // an eye on live import throughput. Run with `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_live_import() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'Benchmark', 'http://bench.invalid/', 'xtream')", []).unwrap();
+
+        let categories: Vec<XtreamCategory> = (0..500)
+            .map(|i| XtreamCategory { category_id: i.to_string(), category_name: format!("Category {}", i) })
+            .collect();
+        let fixture = synthetic_live_streams_fixture(100_000, categories.len());
+
+        let (download, import, streams) = run_live_import_benchmark(&conn, &fixture, &categories).unwrap();
+        // Second import of the same body, where every channel diffs as unchanged
+        let (_, reimport, _) = run_live_import_benchmark(&conn, &fixture, &categories).unwrap();
+        assert_eq!(streams, 100_000);
+        println!("[BENCH] {} streams, {} bytes: decode + stage {:.2?}, import {:.2?}, reimport {:.2?}", streams, fixture.len(), download, import, reimport);
+    }
+
+    #[test]
+    fn refresh_runs_let_hosts_take_turns() {
+        let playlists: Vec<Playlist> = ["http://a.tv/", "http://a.tv:8080/", "http://b.tv/", "/home/me/list.m3u", "http://A.tv/x", "http://b.tv/y"]
+            .iter().map(|url| Playlist { url: url.to_string(), ..Default::default() }).collect();
+        let order: Vec<usize> = interleave_by_host(playlists).into_iter().map(|(index, _)| index).collect();
+        assert_eq!(order, vec![0, 2, 3, 1, 5, 4]);
+    }
+
+    #[tokio::test]
+    async fn refresh_slots_respect_global_and_per_host_limits() {
+        use std::sync::atomic::AtomicUsize;
+        // In the order interleave_by_host spawns them
+        let hosts = ["a", "b", "c", "a", "b", "c", "a", "b", "c", "a"];
+        let slots = Arc::new(tokio::sync::Semaphore::new(3));
+        let host_slots: HashMap<&str, Arc<tokio::sync::Semaphore>> = ["a", "b", "c"].into_iter().map(|h| (h, Arc::new(tokio::sync::Semaphore::new(1)))).collect();
+        // Refreshes running overall and per host, and the most seen at once
+        let running = Arc::new(AtomicUsize::new(0));
+        let peak = Arc::new(AtomicUsize::new(0));
+        let per_host: Arc<Mutex<HashMap<&str, (usize, usize)>>> = Arc::default();
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for host in hosts {
+            let (slots, host_slot) = (slots.clone(), host_slots[host].clone());
+            let (running, peak, per_host) = (running.clone(), peak.clone(), per_host.clone());
+            tasks.spawn(async move {
+                let _permits = acquire_refresh_slots(&slots, Some(&host_slot)).await.unwrap();
+                peak.fetch_max(running.fetch_add(1, Ordering::SeqCst) + 1, Ordering::SeqCst);
+                {
+                    let mut per_host = per_host.lock().unwrap();
+                    let (now, max) = per_host.entry(host).or_default();
+                    *now += 1;
+                    *max = (*max).max(*now);
+                }
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                per_host.lock().unwrap().get_mut(host).unwrap().0 -= 1;
+                running.fetch_sub(1, Ordering::SeqCst);
+            });
+        }
+        while tasks.join_next().await.is_some() {}
+
+        assert_eq!(peak.load(Ordering::SeqCst), 3);
+        assert!(per_host.lock().unwrap().values().all(|(_, max)| *max == 1));
+    }
+
+    #[tokio::test]
+    async fn refresh_slots_let_other_hosts_run_while_one_host_queues() {
+        let slots = Arc::new(tokio::sync::Semaphore::new(2));
+        let host_a = Arc::new(tokio::sync::Semaphore::new(1));
+        let host_b = tokio::sync::Semaphore::new(1);
+        // Keeps the refreshes on host a running until the one on host b got its slots
+        let release = Arc::new(tokio::sync::Semaphore::new(0));
+
+        let mut tasks = tokio::task::JoinSet::new();
+        for _ in 0..3 {
+            let (slots, host_a, release) = (slots.clone(), host_a.clone(), release.clone());
+            tasks.spawn(async move {
+                let _permits = acquire_refresh_slots(&slots, Some(&host_a)).await.unwrap();
+                let _ = release.acquire().await;
+            });
+        }
+        // Let the host a refreshes take or queue for their slots first
+        tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+
+        let other_host = tokio::time::timeout(std::time::Duration::from_secs(1), acquire_refresh_slots(&slots, Some(&host_b))).await;
+        assert!(other_host.is_ok(), "a refresh on another host waited behind a host at its limit");
+        drop(other_host);
+        release.add_permits(3);
+        while tasks.join_next().await.is_some() {}
+    }
+
+    #[test]
+    fn refresh_errors_keep_the_kind_they_were_created_with() {
+        assert_eq!(status_error(reqwest::StatusCode::FORBIDDEN).kind, ErrorKind::Auth);
+        assert_eq!(status_error(reqwest::StatusCode::BAD_GATEWAY).kind, ErrorKind::Provider);
+        assert_eq!(parse_m3u("<html>Not found</html>").unwrap_err().kind, ErrorKind::Parse);
+
+        let info: XtreamAccountInfo = serde_json::from_str(r#"{"user_info":{"auth":1,"status":"Expired","exp_date":"1704067200"}}"#).unwrap();
+        let expired = info.status_error().unwrap();
+        assert_eq!((expired.kind, expired.message.as_str()), (ErrorKind::Auth, "Authentication failed: account expired on 2024-01-01"));
+
+        // Wording that mentions another kind does not change it
+        let error = ErrorKind::Timeout.error("Authentication failed: upstream said so");
+        assert_eq!(error.kind, ErrorKind::Timeout);
+        assert_eq!(RefreshError::from("disk I/O error".to_string()).kind, ErrorKind::Internal);
+        // Only the kind marks a cancellation, not the wording
+        assert!(!ErrorKind::Provider.error(REFRESH_CANCELLED).is_cancelled());
+        assert_eq!(String::from(error), "Request timed out: Authentication failed: upstream said so");
+    }
+
+    #[test]
+    fn xtream_links_yield_server_base_and_credentials() {
+        let parsed = |link: &str| parse_xtream_link(link).map(|(base, user, pass)| format!("{} {} {}", base, user, pass));
+        assert_eq!(parsed(" http://tv.example:8080/get.php?username=U&password=P&type=m3u_plus ").as_deref(), Some("http://tv.example:8080/ U P"));
+        assert_eq!(parsed("https://tv.example/panel/player_api.php?password=P&username=U").as_deref(), Some("https://tv.example/panel/ U P"));
+        assert_eq!(parsed("http://tv.example/xmltv.php?username=U&password=P").as_deref(), Some("http://tv.example/ U P"));
+        assert_eq!(parsed("http://tv.example:80/live/U/P/123.ts").as_deref(), Some("http://tv.example/ U P"));
+        assert_eq!(parsed("http://tv.example/x/series/U/P/9.mkv").as_deref(), Some("http://tv.example/x/ U P"));
+        assert_eq!(parsed("http://tv.example/get.php?username=U"), None);
+        assert_eq!(parsed("http://tv.example/live/U/P"), None);
+        assert_eq!(parsed("http://tv.example/playlist.m3u"), None);
+        assert_eq!(parsed("ftp://tv.example/get.php?username=U&password=P"), None);
+    }
+
+    #[test]
+    fn m3u_playlists_only_convert_for_xtream_api_links() {
+        let normalize = |kind: &str, link: &str, user: Option<&str>| {
+            let (mut kind, mut url, mut username, mut password) = (kind.to_string(), link.to_string(), user.map(str::to_string), None);
+            normalize_playlist_source(&mut kind, &mut url, &mut username, &mut password);
+            (kind, url, username, password)
+        };
+        let (kind, url, username, password) = normalize("m3u", "http://tv.example/get.php?username=U&password=P&type=m3u_plus", Some("Typed"));
+        assert_eq!((kind.as_str(), url.as_str()), ("xtream", "http://tv.example/"));
+        assert_eq!((username.as_deref(), password.as_deref()), (Some("Typed"), Some("P")));
+
+        // A plain M3U on a stream-like path stays an M3U unless Xtream was picked
+        let (kind, url, _, _) = normalize("m3u", "http://cdn.example/live/news/hd/list.m3u", None);
+        assert_eq!((kind.as_str(), url.as_str()), ("m3u", "http://cdn.example/live/news/hd/list.m3u"));
+        let (kind, url, username, _) = normalize("xtream", "http://tv.example/live/U/P/1.ts", None);
+        assert_eq!((kind.as_str(), url.as_str(), username.as_deref()), ("xtream", "http://tv.example/", Some("U")));
+        let (kind, url, _, _) = normalize("stalker", "http://tv.example/get.php?username=U&password=P", None);
+        assert_eq!((kind.as_str(), url.as_str()), ("stalker", "http://tv.example/get.php?username=U&password=P"));
+    }
+
+    #[test]
+    fn extinf_attributes_keep_quoted_commas() {
+        let (attributes, title) = parse_extinf(r#"-1 tvg-name="News, Live" TVG-ID="news.uk" group-title="UK, News",News, Live HD"#);
+        assert_eq!(title, "News, Live HD");
+        assert_eq!(attributes.get("tvg-name").map(String::as_str), Some("News, Live"));
+        assert_eq!(attributes.get("tvg-id").map(String::as_str), Some("news.uk"));
+        assert_eq!(attributes.get("group-title").map(String::as_str), Some("UK, News"));
+    }
+
+    #[test]
+    fn extinf_without_title_or_attribute_values() {
+        let (attributes, title) = parse_extinf(r#"-1 tvg-logo="" tvg-chno=7 catchup,"#);
+        assert_eq!(title, "");
+        assert_eq!(attributes.get("tvg-logo").map(String::as_str), Some(""));
+        assert_eq!(attributes.get("tvg-chno").map(String::as_str), Some("7"));
+        assert!(!attributes.contains_key("catchup"));
+
+        let (attributes, title) = parse_extinf(r#"0 tvg-id="a""#);
+        assert_eq!((attributes.len(), title.as_str()), (1, ""));
+        let (attributes, title) = parse_extinf("-1,Plain");
+        assert_eq!((attributes.len(), title.as_str()), (0, "Plain"));
+    }
+
+    #[test]
+    fn m3u_entries_pair_extinf_with_the_next_url() {
+        let playlist = "\u{feff}#EXTM3U x-tvg-url=\"http://guide\"\n\n#EXTINF:-1 tvg-id=\"a\",One\n#EXTGRP:Sports\n#EXTVLCOPT:http-user-agent=x\nhttp://s/1\n\
+            http://s/orphan\n#EXTINF:-1 tvg-name=\"Two\"\n  http://s/2  \n#EXTINF:-1,Cut off\n";
+        let entries = parse_m3u(playlist).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].title.as_str(), entries[0].group.as_deref(), entries[0].url.as_str()), ("One", Some("Sports"), "http://s/1"));
+        assert_eq!((entries[1].title.as_str(), entries[1].url.as_str()), ("", "http://s/2"));
+        assert!(parse_m3u("http://s/1\n").is_err());
+    }
+
+    #[test]
+    fn stalker_paging_without_page_info_stops_at_a_short_or_empty_page() {
+        // Portal reports both: the total decides
+        assert!(!stalker_last_page(14, 14, Some(14), Some(40), 28));
+        assert!(stalker_last_page(12, 14, Some(14), Some(40), 40));
+        // No total or page size: full pages continue, a shorter or empty one ends the list
+        assert!(!stalker_last_page(14, 14, None, None, 28));
+        assert!(stalker_last_page(3, 14, None, None, 31));
+        assert!(stalker_last_page(0, 14, None, None, 28));
+        // Page size without a total
+        assert!(!stalker_last_page(10, 10, Some(10), None, 20));
+        assert!(stalker_last_page(9, 10, Some(10), None, 29));
+    }
+
+    #[tokio::test]
+    async fn m3u_duplicate_urls_keep_one_channel_per_listing() {
+        let path = std::env::temp_dir().join(format!("rebootv-m3u-dupes-{}.m3u", std::process::id()));
+        std::fs::write(&path, "#EXTM3U\n\
+            #EXTINF:-1 group-title=\"News\",One\nhttp://tv.invalid/1\n\
+            #EXTINF:-1 group-title=\"Sport\",One\nhttp://tv.invalid/1\n\
+            #EXTINF:-1 group-title=\"News\",One\nhttp://tv.invalid/1\n\
+            #EXTINF:-1 group-title=\"News\",Two\nhttp://tv.invalid/2\n").unwrap();
+        let playlist = Playlist { id: 1, r#type: "m3u".to_string(), url: path.to_string_lossy().into_owned(), ..Default::default() };
+        let task = RefreshTask::untracked();
+        let (categories, entries) = fetch_m3u_live(&Client::new(), &playlist, &task).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let ids: HashSet<i64> = entries.iter().map(|e| e.remote_id).collect();
+        assert_eq!(ids.len(), 4);
+        // The first listing of a URL keeps the id it always had
+        assert_eq!(entries[0].remote_id, i64::from(crc32fast::hash(b"1-http://tv.invalid/1")));
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'M3U', 'list.m3u', 'm3u')", []).unwrap();
+        let summary = save_live_channels(&conn, &task, 1, &categories, &entries).unwrap();
+        assert_eq!(summary.added.len(), entries.len());
+    }
+
+    #[test]
+    fn channels_without_logo_read_back_as_none() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'p', 'http://x/', 'm3u')", []).unwrap();
+        conn.execute("INSERT INTO channels (id, playlist_id, name, stream_url, category) VALUES (1, 1, 'A', 'u', 'News')", []).unwrap();
+        assert_eq!(fetch_single_channel(&conn, 1).unwrap().logo_url, None);
+    }
+
+    fn split_json_array(body: &[u8], chunk_size: usize) -> Result<Vec<String>, String> {
+        let mut splitter = JsonArraySplitter::default();
+        let mut out = Vec::new();
+        for chunk in body.chunks(chunk_size) {
+            splitter.feed(chunk, &mut out)?;
+        }
+        splitter.finish()?;
+        Ok(out.into_iter().map(|item| String::from_utf8(item).unwrap()).collect())
+    }
+
+    #[test]
+    fn json_array_splitter_ignores_brackets_and_quotes_inside_strings() {
+        let body = br#"[{"name":"A [HD] {1}","tag":"\"]\""},"x]\\",{"n":[1,[2]]},42]"#;
+        let expected = vec![r#"{"name":"A [HD] {1}","tag":"\"]\""}"#, r#""x]\\""#, r#"{"n":[1,[2]]}"#, "42"];
+        // Every chunk size, so boundaries fall inside strings, escapes and numbers
+        for size in 1..=body.len() {
+            assert_eq!(split_json_array(body, size).unwrap(), expected, "chunk size {}", size);
+        }
+    }
+
+    #[test]
+    fn json_array_splitter_handles_empty_and_invalid_bodies() {
+        assert!(split_json_array(b"[]", 1).unwrap().is_empty());
+        assert!(split_json_array(b"\xEF\xBB\xBF \n[ ]", 1).unwrap().is_empty());
+        assert!(split_json_array(br#"{"user_info":{"auth":0}}"#, 4).is_err());
+        assert!(split_json_array(br#"[{"a":1},{"b":"#, 4).is_err());
+    }
+
+    #[test]
+    fn xtream_live_decoder_builds_entries_fed_byte_by_byte() {
+        let account = XtreamAccount { base_url: "http://tv.invalid/".to_string(), username: "u".to_string(), password: "p".to_string() };
+        let categories = vec![XtreamCategory { category_id: "3".to_string(), category_name: "News".to_string() }];
+        let body = br#"[{"num":1,"name":"One \"HD\"","stream_id":"11","category_id":"3","tv_archive":1,"tv_archive_duration":"5"},
+            {"num":2,"name":"Zero","stream_id":0},{"num":"3","name":"Two","stream_id":12,"category_id":"9"}]"#;
+        let mut decoder = XtreamLiveDecoder::new(account, Some("ts"), &categories);
+        let mut entries = Vec::new();
+        for b in body.iter() {
+            entries.extend(decoder.feed(std::slice::from_ref(b)).unwrap());
+        }
+        decoder.finish().unwrap();
+
+        // The stream without an id is skipped but still counts towards the order
+        assert_eq!(entries.len(), 2);
+        assert_eq!((entries[0].remote_id, entries[0].name.as_str(), entries[0].category.as_str()), (11, "One \"HD\"", "News"));
+        assert_eq!(entries[0].stream_url, "http://tv.invalid/live/u/p/11.ts");
+        assert_eq!((entries[0].catchup_days, entries[0].channel_number), (5, Some(1)));
+        assert_eq!((entries[1].remote_id, entries[1].category.as_str(), entries[1].sort_order), (12, "Uncategorized", 2));
+        assert_eq!(entries[1].channel_number, Some(3));
+    }
+
+    #[test]
+    fn staged_live_channels_import_in_order_and_keep_ids() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'Staged', 'http://tv.invalid/', 'xtream')", []).unwrap();
+        let entry = |remote_id: i64, name: &str, sort_order: i64| LiveChannelEntry {
+            remote_id, name: name.to_string(), stream_url: format!("http://tv.invalid/live/u/p/{}.ts", remote_id),
+            category: "Uncategorized".to_string(), category_remote_id: None, logo_url: None, epg_channel_id: None,
+            sort_order, channel_number: None, catchup_days: 0, epg_shift: None,
+        };
+        let task = RefreshTask::untracked();
+        let stored = |conn: &rusqlite::Connection| -> Vec<(i64, String)> {
+            let mut stmt = conn.prepare("SELECT id, name FROM channels ORDER BY sort_order").unwrap();
+            stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?))).unwrap().map(|r| r.unwrap()).collect()
+        };
+
+        begin_live_staging(&conn).unwrap();
+        stage_live_entries(&conn, &[entry(11, "One", 0), entry(12, "Two", 1)]).unwrap();
+        stage_live_entries(&conn, &[entry(13, "Three", 2), entry(11, "One again", 3)]).unwrap();
+        let summary = save_staged_live_channels(&conn, &task, 1, &[]).unwrap();
+        let first = stored(&conn);
+        assert_eq!(summary.added, vec!["One", "Two", "Three"]);
+        assert_eq!(first.iter().map(|(_, name)| name.as_str()).collect::<Vec<_>>(), vec!["One", "Two", "Three"]);
+
+        // A second run starts from an empty staging table
+        begin_live_staging(&conn).unwrap();
+        stage_live_entries(&conn, &[entry(13, "Three", 0), entry(11, "One", 1)]).unwrap();
+        let summary = save_staged_live_channels(&conn, &task, 1, &[]).unwrap();
+        assert_eq!((summary.removed.clone(), summary.updated), (vec!["Two".to_string()], 2));
+        assert_eq!(stored(&conn), vec![(first[2].0, "Three".to_string()), (first[0].0, "One".to_string())]);
+        assert_eq!(conn.query_row("SELECT COUNT(*) FROM temp.live_staging", [], |row| row.get::<_, i64>(0)).unwrap(), 0);
+    }
+
+    #[test]
+    fn cancelled_live_import_rolls_back_as_cancelled() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'Staged', 'http://tv.invalid/', 'xtream')", []).unwrap();
+        begin_live_staging(&conn).unwrap();
+        conn.execute("INSERT INTO temp.live_staging (remote_id, name, stream_url, category, sort_order, catchup_days) VALUES (11, 'One', 'http://tv.invalid/11', 'News', 0, 0)", []).unwrap();
+
+        let task = RefreshTask::untracked();
+        task.cancelled.store(true, Ordering::SeqCst);
+        let error = save_staged_live_channels(&conn, &task, 1, &[LiveCategory { remote_id: "3".to_string(), name: "News".to_string() }]).unwrap_err();
+        assert_eq!((error.kind, error.is_cancelled()), (ErrorKind::Cancelled, true));
+        assert_eq!(conn.query_row("SELECT COUNT(*) FROM categories", [], |row| row.get::<_, i64>(0)).unwrap(), 0);
+    }
+
+    // Writes a synthetic XMLTV guide shaped like a full provider guide: one programme every
+    // 30 minutes per channel, with entities, attributes and elements the importer skips.
+    // Returns the number of programmes written.
+    fn write_synthetic_guide(path: &std::path::Path, channel_count: usize, days: usize) -> Result<usize, String> {
+        use std::io::Write;
+        let mut out = std::io::BufWriter::new(std::fs::File::create(path).map_err(|e| e.to_string())?);
+        let io = |e: std::io::Error| e.to_string();
+        writeln!(out, "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE tv SYSTEM \"xmltv.dtd\">\n<tv generator-info-name=\"benchmark\">").map_err(io)?;
+        for i in 0..channel_count {
+            writeln!(out, "  <channel id=\"channel{}.uk\"><display-name lang=\"en\">UK: Channel {} HD</display-name><icon src=\"http://logos.example.com/{}.png\"/></channel>", i, i, i).map_err(io)?;
+        }
+        let first = chrono::DateTime::from_timestamp(1_704_067_200, 0).unwrap_or_default();
+        let slots = days * 48;
+        for i in 0..channel_count {
+            for slot in 0..slots {
+                let start = first + chrono::Duration::minutes(30 * slot as i64);
+                let stop = start + chrono::Duration::minutes(30);
+                writeln!(
+                    out,
+                    "  <programme start=\"{} +0000\" stop=\"{} +0000\" channel=\"channel{}.uk\"><title lang=\"en\">Show {} &amp; Friends</title><sub-title lang=\"en\">Part {}</sub-title><desc lang=\"en\">Episode {} of a long-running series, with the usual &lt;markup&gt; in the text.</desc><category lang=\"en\">Entertainment</category><episode-num system=\"xmltv_ns\">{}.{}.</episode-num><episode-num system=\"onscreen\">S{:02}E{:02}</episode-num><credits><presenter>Host {}</presenter></credits></programme>",
+                    start.format("%Y%m%d%H%M%S"), stop.format("%Y%m%d%H%M%S"), i, slot % 40, slot % 3 + 1, slot, slot / 48, slot % 48, slot / 48 + 1, slot % 48 + 1, i % 50
+                ).map_err(io)?;
+            }
+        }
+        writeln!(out, "</tv>").map_err(io)?;
+        out.flush().map_err(io)?;
+        Ok(channel_count * slots)
+    }
+
+    // Imports a synthetic guide (2,000 channels over 7 days, a few hundred MB) into an in-memory
+    // database through the same streaming importer a refresh uses. Run with
+    // `cargo test --release -- --ignored --nocapture`.
+    #[test]
+    #[ignore]
+    fn benchmark_epg_import() {
+        let channel_count = 2_000;
+        let guide_path = std::env::temp_dir().join("rebootv-epg-benchmark.xml");
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'Benchmark', 'http://bench.invalid/', 'm3u')", []).unwrap();
+        {
+            // Every fourth channel has no EPG id and is matched by name instead
+            let mut stmt = conn.prepare("INSERT INTO channels (playlist_id, name, stream_url, epg_channel_id) VALUES (1, ?1, ?2, ?3)").unwrap();
+            for i in 0..channel_count {
+                let epg_id = (i % 4 != 0).then(|| format!("channel{}.uk", i));
+                stmt.execute(rusqlite::params![format!("UK: Channel {} HD", i), format!("http://bench.invalid/{}", i), epg_id]).unwrap();
+            }
+        }
+
+        let programmes = write_synthetic_guide(&guide_path, channel_count, 7).unwrap();
+        let bytes = std::fs::metadata(&guide_path).unwrap().len();
+
+        let started = std::time::Instant::now();
+        let targets = build_guide_maps(&conn, Some(1), &HashSet::new()).unwrap();
+        let file = std::fs::File::open(&guide_path).unwrap();
+        let mut parser = GuideParser::new(open_guide(Box::new(file)).unwrap());
+        import_guide(&conn, &mut parser, &targets, "benchmark", GuideTiming::default()).unwrap();
+        let import = started.elapsed();
+        let _ = std::fs::remove_file(&guide_path);
+
+        let entries: usize = conn.query_row("SELECT COUNT(*) FROM epg_entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(entries, programmes);
+        println!("[BENCH] {} channels, {} programmes, {} bytes: import {:.2?}", channel_count, programmes, bytes, import);
+    }
+
+    const SAMPLE_GUIDE: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<tv><channel id="one.uk"><display-name>One &amp; Only</display-name></channel>
+<programme start="20240101100000 +0000" stop="20240101110000 +0000" channel="one.uk"><title>News</title><desc>Headlines</desc></programme>
+<programme start="20240101110000 +0000" channel="one.uk"><title>No stop</title></programme>
+<programme start="20240101120000 +0000" stop="20240101130000 +0000" channel="one.uk"><title><![CDATA[Film & Co]]></title></programme>
+</tv>"#;
+
+    // Channel ids and programme titles of a guide, in document order
+    fn read_guide(input: Box<dyn std::io::Read + Send>) -> Result<Vec<String>, String> {
+        let mut parser = GuideParser::new(open_guide(input)?);
+        let mut items = Vec::new();
+        while let Some(event) = parser.next_event()? {
+            items.push(match event {
+                GuideEvent::Channel { id, display_name } => format!("{}={}", id, display_name.unwrap_or_default()),
+                GuideEvent::Programme(programme) => format!("{}@{}", programme.title, programme.start),
+            });
+        }
+        Ok(items)
+    }
+
+    // Feeds a body to a GuideBodyReader a few bytes at a time, as a slow download would
+    fn guide_body_reader(body: Vec<u8>, chunk_size: usize, complete: bool) -> GuideBodyReader {
+        let (tx, rx) = tokio::sync::mpsc::channel(4);
+        std::thread::spawn(move || {
+            for chunk in body.chunks(chunk_size) {
+                if tx.blocking_send(GuideChunk::Data(chunk.to_vec())).is_err() { return; }
+            }
+            if complete { let _ = tx.blocking_send(GuideChunk::End); }
+        });
+        GuideBodyReader::new(rx)
+    }
+
+    #[test]
+    fn guide_parser_skips_programmes_without_stop() {
+        let items = read_guide(Box::new(std::io::Cursor::new(SAMPLE_GUIDE.as_bytes().to_vec()))).unwrap();
+        assert_eq!(items, vec!["one.uk=One & Only", "News@20240101100000 +0000", "Film & Co@20240101120000 +0000"]);
+    }
+
+    #[test]
+    fn guide_parser_reads_programmes_split_across_chunks() {
+        let expected = read_guide(Box::new(std::io::Cursor::new(SAMPLE_GUIDE.as_bytes().to_vec()))).unwrap();
+        for size in [1, 3, 7, 64] {
+            let reader = guide_body_reader(SAMPLE_GUIDE.as_bytes().to_vec(), size, true);
+            assert_eq!(read_guide(Box::new(reader)).unwrap(), expected, "chunk size {}", size);
+        }
+        // A download that stops without End is not a complete guide
+        let reader = guide_body_reader(SAMPLE_GUIDE.as_bytes()[..200].to_vec(), 16, false);
+        assert!(read_guide(Box::new(reader)).is_err());
+    }
+
+    #[test]
+    fn open_guide_sniffs_gzip_and_xz() {
+        use std::io::Write;
+        let expected = read_guide(Box::new(std::io::Cursor::new(SAMPLE_GUIDE.as_bytes().to_vec()))).unwrap();
+
+        let mut gzip = flate2::write::GzEncoder::new(Vec::new(), flate2::Compression::default());
+        gzip.write_all(SAMPLE_GUIDE.as_bytes()).unwrap();
+        let gzip = gzip.finish().unwrap();
+        let mut xz = xz2::write::XzEncoder::new(Vec::new(), 6);
+        xz.write_all(SAMPLE_GUIDE.as_bytes()).unwrap();
+        let xz = xz.finish().unwrap();
+
+        assert_eq!(detect_guide_compression(&gzip).unwrap(), GuideCompression::Gzip);
+        assert_eq!(detect_guide_compression(&xz).unwrap(), GuideCompression::Xz);
+        assert_eq!(detect_guide_compression(b"\xEF\xBB\xBF\n<?xml").unwrap(), GuideCompression::None);
+        assert!(detect_guide_compression(b"{\"error\":true}").is_err());
+        assert_eq!(read_guide(Box::new(std::io::Cursor::new(gzip.clone()))).unwrap(), expected);
+        assert_eq!(read_guide(Box::new(std::io::Cursor::new(xz))).unwrap(), expected);
+        // Compressed bodies arriving in small chunks too
+        assert_eq!(read_guide(Box::new(guide_body_reader(gzip, 5, true))).unwrap(), expected);
+    }
+
+    #[test]
+    fn xmltv_ns_episode_numbers_are_one_based() {
+        assert_eq!(parse_xmltv_ns("1.4.0/1"), (Some(2), Some(5)));
+        assert_eq!(parse_xmltv_ns("0/3.9/12."), (Some(1), Some(10)));
+        assert_eq!(parse_xmltv_ns(" . 9/12 . "), (None, Some(10)));
+        assert_eq!(parse_xmltv_ns("2..1/2"), (Some(3), None));
+        assert_eq!(parse_xmltv_ns(""), (None, None));
+        assert_eq!(parse_xmltv_ns("x.-1."), (None, None));
+    }
+
+    #[test]
+    fn episode_label_prefers_numbers_over_onscreen_text() {
+        assert_eq!(episode_label(Some(2), Some(5), Some("Ep 3")).as_deref(), Some("S02E05"));
+        assert_eq!(episode_label(None, Some(10), None).as_deref(), Some("E10"));
+        assert_eq!(episode_label(None, None, Some(" Ep 3 ")).as_deref(), Some("Ep 3"));
+        // A season alone says nothing about the episode
+        assert_eq!(episode_label(Some(2), None, Some("Pilot")).as_deref(), Some("Pilot"));
+        assert_eq!(episode_label(None, None, Some("  ")), None);
+    }
+
+    #[test]
+    fn xmltv_times_read_offsets_and_server_timezones() {
+        let utc = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&chrono::Utc);
+        let new_york = Some(chrono_tz::America::New_York);
+        assert_eq!(parse_xmltv_time("20240115100000 +0200", None), Some(utc("2024-01-15T08:00:00Z")));
+        assert_eq!(parse_xmltv_time("20240115100000-0500", None), Some(utc("2024-01-15T15:00:00Z")));
+        // An explicit offset wins over the server timezone
+        assert_eq!(parse_xmltv_time("20240115100000 +0000", new_york), Some(utc("2024-01-15T10:00:00Z")));
+        assert_eq!(parse_xmltv_time("20240115100000", None), Some(utc("2024-01-15T10:00:00Z")));
+        assert_eq!(parse_xmltv_time("20240115100000", new_york), Some(utc("2024-01-15T15:00:00Z")));
+        assert_eq!(parse_xmltv_time("20240715100000", new_york), Some(utc("2024-07-15T14:00:00Z")));
+        assert_eq!(parse_xmltv_time("2024-01-15 10:00", new_york), None);
+    }
+
+    #[test]
+    fn xmltv_times_across_dst_changes() {
+        let utc = |s: &str| chrono::DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&chrono::Utc);
+        let new_york = Some(chrono_tz::America::New_York);
+        // 02:30 does not exist on 10 March 2024 in New York; it reads as EST
+        assert_eq!(parse_xmltv_time("20240310023000", new_york), Some(utc("2024-03-10T07:30:00Z")));
+        assert_eq!(parse_xmltv_time("20240310033000", new_york), Some(utc("2024-03-10T07:30:00Z")));
+        // 01:30 happens twice on 3 November 2024; the first one (EDT) is kept
+        assert_eq!(parse_xmltv_time("20241103013000", new_york), Some(utc("2024-11-03T05:30:00Z")));
+    }
+
+    #[test]
+    fn guide_import_adds_global_source_and_channel_shifts() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute(
+            "INSERT INTO settings (key, value) VALUES ('app_settings', ?1)",
+            [r#"{"defaultView":"live","refreshOnStart":false,"minimizeToTray":false,"exitToTray":false,"mpvParams":"","startVolume":100,
+                "hwAccel":false,"bufferSize":"","epgTimeOffset":60,"epgRefreshFrequency":24}"#],
+        ).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type, server_timezone) VALUES (1, 'p', 'http://x/', 'm3u', 'America/New_York')", []).unwrap();
+        conn.execute("INSERT INTO channels (id, playlist_id, name, stream_url, epg_channel_id, epg_shift) VALUES (1, 1, 'A', 'u1', 'a', -15)", []).unwrap();
+        conn.execute("INSERT INTO channels (id, playlist_id, name, stream_url, epg_channel_id) VALUES (2, 1, 'B', 'u2', 'a')", []).unwrap();
+        conn.execute("INSERT INTO epg_sources (id, name, url, playlist_id, time_offset) VALUES (1, 's', 'http://x/guide.xml', 1, 30)", []).unwrap();
+
+        let jobs = collect_guide_jobs(&conn).unwrap();
+        assert_eq!(jobs.len(), 1);
+        let timing = GuideTiming { retention: None, ..jobs[0].timing };
+        assert_eq!(timing.offset_minutes, 90);
+
+        let xml = r#"<tv><programme start="20240115100000" stop="20240115110000" channel="a"><title>Naive</title></programme>
+            <programme start="20240115100000 +0000" stop="20240115110000 +0000" channel="a"><title>Zoned</title></programme></tv>"#;
+        let targets = build_guide_maps(&conn, jobs[0].scope, &HashSet::new()).unwrap();
+        let mut parser = GuideParser::new(open_guide(Box::new(std::io::Cursor::new(xml.as_bytes().to_vec()))).unwrap());
+        import_guide(&conn, &mut parser, &targets, &jobs[0].key, timing).unwrap();
+
+        let start = |channel: i64, title: &str| -> String {
+            conn.query_row("SELECT start_time FROM epg_entries WHERE channel_id = ?1 AND title = ?2", rusqlite::params![channel, title], |row| row.get(0)).unwrap()
+        };
+        // 10:00 New York is 15:00 UTC, then +60 global, +30 source and -15 for channel 1
+        assert_eq!(start(1, "Naive"), "2024-01-15T16:15:00+00:00");
+        assert_eq!(start(2, "Naive"), "2024-01-15T16:30:00+00:00");
+        assert_eq!(start(1, "Zoned"), "2024-01-15T11:15:00+00:00");
+        assert_eq!(start(2, "Zoned"), "2024-01-15T11:30:00+00:00");
+    }
+
+    #[test]
+    fn epg_entries_from_before_source_keys_are_kept_as_legacy() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE epg_entries (id INTEGER PRIMARY KEY, channel_id INTEGER NOT NULL, title TEXT NOT NULL, description TEXT,
+                start_time TEXT NOT NULL, end_time TEXT NOT NULL);
+             INSERT INTO epg_entries VALUES (7, 1, 'Old', NULL, '2024-01-01T10:00:00+00:00', '2024-01-01T11:00:00+00:00');"
+        ).unwrap();
+        create_schema(&conn).unwrap();
+        let key: String = conn.query_row("SELECT source_key FROM epg_entries WHERE id = 7", [], |row| row.get(0)).unwrap();
+        assert_eq!(key, EPG_LEGACY_SOURCE_KEY);
+        // Running it again is a no-op
+        create_schema(&conn).unwrap();
+        prune_guide(&conn, &[]).unwrap();
+        let count: i64 = conn.query_row("SELECT COUNT(*) FROM epg_entries", [], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn guide_import_counts_duplicate_programmes() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        create_schema(&conn).unwrap();
+        conn.execute("INSERT INTO playlists (id, name, url, type) VALUES (1, 'p', 'http://x/', 'm3u')", []).unwrap();
+        conn.execute("INSERT INTO channels (id, playlist_id, name, stream_url, epg_channel_id) VALUES (1, 1, 'A', 'u', 'a')", []).unwrap();
+        let xml = r#"<tv><programme start="20240101100000 +0000" stop="20240101110000 +0000" channel="a"><title>News</title></programme>
+            <programme start="20240101100000 +0000" stop="20240101110000 +0000" channel="a"><title>News</title></programme>
+            <programme start="20240101100000 +0000" stop="20240101110000 +0000" channel="a"><title>Other</title></programme></tv>"#;
+        let targets = build_guide_maps(&conn, None, &HashSet::new()).unwrap();
+        for key in ["source:1", "source:2"] {
+            let mut parser = GuideParser::new(open_guide(Box::new(std::io::Cursor::new(xml.as_bytes().to_vec()))).unwrap());
+            let imported = import_guide(&conn, &mut parser, &targets, key, GuideTiming::default()).unwrap();
+            assert_eq!((imported.programmes, imported.duplicates), (2, 1));
+        }
+        // The second guide took the channel over from the first
+        let keys: Vec<String> = conn.prepare("SELECT DISTINCT source_key FROM epg_entries").unwrap()
+            .query_map([], |row| row.get(0)).unwrap().map(|r| r.unwrap()).collect();
+        assert_eq!(keys, vec!["source:2"]);
+    }
+}
@@ -36,9 +36,9 @@ import { IptvService } from '../../services/iptv.service';
         }
         
         <!-- FIX: Only render NgOptimizedImage if URL exists -->
-        @if (channel().logoUrl) {
+        @if (channel().logoUrl; as logoUrl) {
             <img 
-              [ngSrc]="channel().logoUrl" 
+              [ngSrc]="logoUrl" 
               [priority]="priority()" 
               width="64" 
               height="64" 
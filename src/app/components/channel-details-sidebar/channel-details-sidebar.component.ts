@@ -66,8 +66,32 @@ import { NotificationService } from '../../services/notification.service';
           @if (currentProgram(); as program) {
             <div class="p-4">
               <h4 class="text-sm font-semibold uppercase text-gray-400 mb-2">Now Playing</h4>
-              <p class="font-semibold text-gray-100">{{ program.title }}</p>
+              <div class="flex gap-3">
+                @if (program.icon) {
+                  <img [src]="program.icon" [alt]="program.title" loading="lazy" class="w-20 rounded-md object-cover flex-shrink-0 self-start">
+                }
+                <div class="min-w-0">
+                  <p class="font-semibold text-gray-100">
+                    {{ program.title }}
+                    @if (program.isNew || program.isPremiere) {
+                      <span class="ml-1 text-[10px] font-bold uppercase px-1.5 py-0.5 rounded bg-sky-600/80 text-white align-middle">{{ program.isPremiere ? 'Premiere' : 'New' }}</span>
+                    }
+                  </p>
+                  @if (program.episodeLabel || program.subTitle) {
+                    <p class="text-sm text-gray-300">
+                      @if (program.episodeLabel) { <span class="font-mono text-sky-400">{{ program.episodeLabel }}</span> }
+                      {{ program.subTitle }}
+                    </p>
+                  }
+                  @if (programMeta(program); as meta) {
+                    <p class="text-xs text-gray-500 mt-0.5">{{ meta }}</p>
+                  }
+                </div>
+              </div>
               <p class="text-sm text-gray-400 mt-1">{{ program.description }}</p>
+              @if (program.credits?.length) {
+                <p class="text-xs text-gray-500 mt-2 line-clamp-2">{{ creditsLine(program) }}</p>
+              }
               <div class="flex items-center mt-3">
                 <div class="w-full bg-gray-600 rounded-full h-1.5 mr-3">
                   <div class="bg-sky-500 h-1.5 rounded-full" [style.width.%]="programProgress()"></div>
@@ -90,6 +114,9 @@ import { NotificationService } from '../../services/notification.service';
                     <span class="w-20 text-gray-400 flex-shrink-0">{{ program.startTime | date:'shortTime' }}</span>
                     <div class="flex items-center min-w-0">
                       <span class="text-gray-300 truncate" [title]="program.title">{{ program.title }}</span>
+                      @if (program.episodeLabel) {
+                        <span class="ml-2 text-xs font-mono text-gray-500 flex-shrink-0">{{ program.episodeLabel }}</span>
+                      }
                       <div class="relative notification-menu-container ml-2 flex-shrink-0">
                         <button (click)="toggleNotificationMenu($event, program.id)" class="p-1 rounded-full hover:bg-gray-600/50 transition-colors" title="Set a reminder">
                           <svg xmlns="http://www.w3.org/2000/svg" fill="none" viewBox="0 0 24 24" stroke-width="1.5" stroke="currentColor" class="w-4 h-4 text-gray-400">
@@ -114,6 +141,16 @@ import { NotificationService } from '../../services/notification.service';
               }
             </ul>
           </div>
+
+          <!-- Guide shift, for channels whose programmes run early or late -->
+          <div class="p-4 border-t border-gray-700/50 flex items-center justify-between">
+            <h4 class="text-sm font-semibold uppercase text-gray-400">Guide Shift</h4>
+            <div class="flex items-center gap-2">
+              <button (click)="onShiftGuide(channel, -30)" class="px-2 py-1 rounded-md bg-gray-700 hover:bg-gray-600 text-sm" title="Move the guide 30 minutes earlier">−30m</button>
+              <span class="w-16 text-center text-sm text-gray-300 font-mono">{{ shiftLabel(channel.epgShift ?? 0) }}</span>
+              <button (click)="onShiftGuide(channel, 30)" class="px-2 py-1 rounded-md bg-gray-700 hover:bg-gray-600 text-sm" title="Move the guide 30 minutes later">+30m</button>
+            </div>
+          </div>
         </div>
       </aside>
     }
@@ -163,6 +200,22 @@ export class ChannelDetailsSidebarComponent {
       .sort((a, b) => new Date(a.startTime).getTime() - new Date(b.startTime).getTime());
   });
 
+  // Genre, year and ratings on one line, e.g. "Drama, Crime · 2019 · PG-13 · ★ 7/10"
+  programMeta(program: EpgEntry): string {
+    return [
+      program.categories?.join(', '),
+      program.date?.slice(0, 4),
+      program.rating,
+      program.starRating ? `★ ${program.starRating}` : null,
+    ].filter(Boolean).join(' · ');
+  }
+
+  creditsLine(program: EpgEntry): string {
+    return (program.credits ?? [])
+      .map(c => c.role === 'actor' ? (c.character ? `${c.name} (${c.character})` : c.name) : `${c.name} (${c.role})`)
+      .join(', ');
+  }
+
   // --- UPDATED: Play Handler ---
   onPlay(channel: Channel): void {
     console.log('[Sidebar] Playing channel via MPV:', channel.name);
@@ -171,7 +224,7 @@ export class ChannelDetailsSidebarComponent {
     this.iptvService.addToRecentlyWatched(channel);
 
     // 2. Launch Player
-    this.tauriService.playStream(channel.streamUrl)
+    this.tauriService.playStream(channel.streamUrl, channel.playlistId)
       .catch(err => console.error('[Sidebar] Failed to launch player:', err));
   }
   
@@ -179,6 +232,18 @@ export class ChannelDetailsSidebarComponent {
     this.iptvService.toggleFavorite(channel.id);
   }
 
+  onShiftGuide(channel: Channel, minutes: number): void {
+    const shift = (channel.epgShift ?? 0) + minutes;
+    if (Math.abs(shift) <= 24 * 60) this.iptvService.setChannelEpgShift(channel.id, shift);
+  }
+
+  shiftLabel(minutes: number): string {
+    if (minutes === 0) return 'None';
+    const abs = Math.abs(minutes);
+    const mins = abs % 60;
+    return `${minutes > 0 ? '+' : '-'}${Math.floor(abs / 60)}h${mins ? ` ${mins}m` : ''}`;
+  }
+
   onClose(): void {
     this.iptvService.selectChannel(null);
   }
@@ -173,7 +173,7 @@ export class LiveTvComponent {
     this.iptvService.addToRecentlyWatched(channel);
 
     // 2. Launch Player
-    this.tauriService.playStream(channel.streamUrl)
+    this.tauriService.playStream(channel.streamUrl, channel.playlistId)
       .catch(err => console.error('[LiveTv] Failed to launch player:', err));
   }
 
@@ -1,6 +1,6 @@
 import { Component, ChangeDetectionStrategy, input, output, effect, inject, computed, signal } from '@angular/core';
 import { CommonModule } from '@angular/common';
-import { Playlist } from '../../models/iptv.models';
+import { HttpProfile, Playlist } from '../../models/iptv.models';
 import { FormGroup, FormControl, Validators, ReactiveFormsModule } from '@angular/forms';
 import { NotificationService } from '../../services/notification.service';
 
@@ -28,6 +28,10 @@ import { NotificationService } from '../../services/notification.service';
           <div>
             <label for="url-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">URL</label>
             <input type="text" id="url-{{uniqueId}}" formControlName="url" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" [placeholder]="urlPlaceholder()">
+          </div>
+          <div>
+            <label for="backupUrls-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">Backup URLs <span class="text-gray-500">(one per line, tried in order if the URL fails)</span></label>
+            <textarea id="backupUrls-{{uniqueId}}" formControlName="backupUrls" rows="2" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" [placeholder]="urlPlaceholder()"></textarea>
           </div>
             @if (playlistForm.get('type')?.value === 'xtream') {
             <div class="grid grid-cols-2 gap-4">
@@ -47,6 +51,37 @@ import { NotificationService } from '../../services/notification.service';
                 <input type="text" id="macAddress-{{uniqueId}}" formControlName="macAddress" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="00:1A:79:XX:XX:XX">
             </div>
           }
+          <details class="rounded-lg border border-gray-700 p-3">
+            <summary class="text-sm font-medium text-gray-300 cursor-pointer">Connection settings</summary>
+            <div class="space-y-4 mt-4">
+              <div>
+                <label for="userAgent-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">User Agent</label>
+                <input type="text" id="userAgent-{{uniqueId}}" formControlName="userAgent" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="Default">
+              </div>
+              <div>
+                <label for="headers-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">Extra Headers <span class="text-gray-500">(Name: value, one per line)</span></label>
+                <textarea id="headers-{{uniqueId}}" formControlName="headers" rows="2" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="Referer: http://server/"></textarea>
+              </div>
+              <div>
+                <label for="proxy-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">Proxy</label>
+                <input type="text" id="proxy-{{uniqueId}}" formControlName="proxy" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="http://host:port or socks5://host:port">
+              </div>
+              <div class="grid grid-cols-2 gap-4">
+                <div>
+                  <label for="connectTimeout-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">Connect Timeout (s)</label>
+                  <input type="number" min="1" id="connectTimeout-{{uniqueId}}" formControlName="connectTimeoutSecs" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="15">
+                </div>
+                <div>
+                  <label for="readTimeout-{{uniqueId}}" class="block mb-2 text-sm font-medium text-gray-300">Read Timeout (s)</label>
+                  <input type="number" min="1" id="readTimeout-{{uniqueId}}" formControlName="readTimeoutSecs" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block w-full p-2.5" placeholder="120">
+                </div>
+              </div>
+              <label class="flex items-center gap-2 text-sm text-gray-300">
+                <input type="checkbox" formControlName="acceptInvalidCerts" class="rounded bg-gray-700 border-gray-600">
+                Accept self-signed certificates
+              </label>
+            </div>
+          </details>
         </div>
         <div class="flex justify-end gap-3 mt-6 flex-shrink-0">
           <button type="button" (click)="onCancel()" class="px-4 py-2 rounded-lg text-sm font-semibold bg-gray-600 hover:bg-gray-500">Cancel</button>
@@ -83,6 +118,13 @@ export class PlaylistCardComponent {
     username: new FormControl(''),
     password: new FormControl(''),
     macAddress: new FormControl(''),
+    backupUrls: new FormControl(''),
+    userAgent: new FormControl(''),
+    headers: new FormControl(''),
+    proxy: new FormControl(''),
+    connectTimeoutSecs: new FormControl<number | null>(null, [Validators.min(1)]),
+    readTimeoutSecs: new FormControl<number | null>(null, [Validators.min(1)]),
+    acceptInvalidCerts: new FormControl(false),
   });
 
   urlPlaceholder = computed(() => {
@@ -133,12 +175,20 @@ export class PlaylistCardComponent {
     if (this.isEditing() && p) {
       this.playlistForm.reset({
         id: p.id, name: p.name, url: p.url, type: p.type,
-        username: p.username ?? '', password: '', macAddress: p.macAddress ?? ''
+        username: p.username ?? '', password: '', macAddress: p.macAddress ?? '',
+        backupUrls: (p.backupUrls ?? []).join('\n'),
+        userAgent: p.httpProfile?.userAgent ?? '',
+        headers: (p.httpProfile?.headers ?? []).map(h => `${h.name}: ${h.value}`).join('\n'),
+        proxy: p.httpProfile?.proxy ?? '',
+        connectTimeoutSecs: p.httpProfile?.connectTimeoutSecs ?? null,
+        readTimeoutSecs: p.httpProfile?.readTimeoutSecs ?? null,
+        acceptInvalidCerts: p.httpProfile?.acceptInvalidCerts ?? false,
       });
     } else {
       this.playlistForm.reset({
         id: null, name: '', url: '', type: 'xtream',
-        username: '', password: '', macAddress: ''
+        username: '', password: '', macAddress: '', backupUrls: '',
+        userAgent: '', headers: '', proxy: '', connectTimeoutSecs: null, readTimeoutSecs: null, acceptInvalidCerts: false
       });
     }
   }
@@ -150,9 +200,20 @@ export class PlaylistCardComponent {
       return;
     }
     const formValue = this.playlistForm.value;
+    const backupUrls = (formValue.backupUrls ?? '').split('\n').map(u => u.trim()).filter(u => u);
+    const httpProfile: HttpProfile = {
+      userAgent: formValue.userAgent?.trim() || null,
+      headers: (formValue.headers ?? '').split('\n')
+        .filter(line => line.indexOf(':') > 0)
+        .map(line => ({ name: line.slice(0, line.indexOf(':')).trim(), value: line.slice(line.indexOf(':') + 1).trim() })),
+      proxy: formValue.proxy?.trim() || null,
+      connectTimeoutSecs: formValue.connectTimeoutSecs || null,
+      readTimeoutSecs: formValue.readTimeoutSecs || null,
+      acceptInvalidCerts: !!formValue.acceptInvalidCerts,
+    };
     if (this.isAdding()) {
-      const { id, ...newPlaylistData } = formValue;
-      this.add.emit(newPlaylistData as Omit<Playlist, 'id' | 'status' | 'isActive'>);
+      const { id, userAgent, headers, proxy, connectTimeoutSecs, readTimeoutSecs, acceptInvalidCerts, ...newPlaylistData } = formValue;
+      this.add.emit({ ...newPlaylistData, backupUrls, httpProfile } as Omit<Playlist, 'id' | 'status' | 'isActive'>);
     } else {
       const originalPlaylist = this.playlist();
       if (!originalPlaylist) return;
@@ -161,6 +222,7 @@ export class PlaylistCardComponent {
         ...originalPlaylist,
         name: formValue.name!, url: formValue.url!, type: formValue.type!,
         username: formValue.username || undefined, macAddress: formValue.macAddress || undefined,
+        backupUrls, httpProfile,
       };
        // Only include password if it has been changed
       if (formValue.password) {
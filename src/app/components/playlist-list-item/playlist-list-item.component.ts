@@ -34,7 +34,14 @@ import { Playlist } from '../../models/iptv.models';
           <div class="min-w-0">
             <h4 class="text-xl font-bold text-gray-100 truncate" [title]="playlist().name">{{ playlist().name }}</h4>
              @if (playlist().status === 'error' && playlist().errorMessage) {
-                <p class="text-xs text-red-400 truncate mt-1" [title]="playlist().errorMessage">{{ playlist().errorMessage }}</p>
+                <p class="text-xs text-red-400 truncate mt-1" [title]="playlist().errorMessage">
+                  @if (playlist().errorCode) {
+                    <span class="uppercase font-semibold mr-1">[{{ playlist().errorCode }}]</span>
+                  }
+                  {{ playlist().errorMessage }}
+                </p>
+            } @else if (playlist().errorCode === 'cancelled' && playlist().errorMessage) {
+                <p class="text-xs text-amber-400 truncate mt-1" [title]="playlist().errorMessage">{{ playlist().errorMessage }}</p>
             }
           </div>
         </div>
@@ -49,6 +56,14 @@ import { Playlist } from '../../models/iptv.models';
         <dl class="grid grid-cols-3 gap-x-4 gap-y-2">
           <dt class="font-medium text-gray-400 col-span-1">URL</dt>
           <dd class="text-gray-200 col-span-2 truncate font-mono text-xs" [title]="playlist().url">{{ playlist().url }}</dd>
+          @if (playlist().backupUrls?.length) {
+              <dt class="font-medium text-gray-400 col-span-1">Servers</dt>
+              <dd class="text-gray-200 col-span-2 font-mono text-xs space-y-0.5">
+                @for (server of [playlist().url].concat(playlist().backupUrls ?? []); track server) {
+                  <div class="truncate" [title]="failureFor(server) ?? server" [class.text-sky-400]="server === playlist().activeUrl" [class.text-red-400]="failureFor(server) && server !== playlist().activeUrl">{{ server }}</div>
+                }
+              </dd>
+          }
           
           @if(playlist().username) {
               <dt class="font-medium text-gray-400 col-span-1">Username</dt>
@@ -116,6 +131,12 @@ export class PlaylistListItemComponent {
     }
   });
 
+  // Tooltip text of the last recorded failure of a server URL
+  failureFor(url: string): string | undefined {
+    const failure = this.playlist().urlFailures?.find(f => f.url === url);
+    return failure ? `${failure.error} (${new Date(failure.failedAt).toLocaleString()})` : undefined;
+  }
+
   onDelete(): void {
     if (confirm(`Are you sure you want to delete "${this.playlist().name}"? This cannot be undone.`)) {
       this.delete.emit(this.playlist().id);
@@ -1,12 +1,13 @@
 import { Component, ChangeDetectionStrategy, inject } from '@angular/core';
 import { CommonModule } from '@angular/common';
-import { ReactiveFormsModule, FormGroup, FormControl } from '@angular/forms';
+import { ReactiveFormsModule, FormGroup, FormControl, Validators } from '@angular/forms';
 // Fix: Import AppSettings to strongly type the form.
 import { SettingsService, AppSettings } from '../../services/settings.service';
 import { debounceTime } from 'rxjs/operators';
 import { NotificationService } from '../../services/notification.service';
 import { TauriService } from '../../services/tauri.service';
 import { IptvService } from '../../services/iptv.service';
+import { EpgSource } from '../../models/iptv.models';
 
 @Component({
   selector: 'app-settings',
@@ -48,6 +49,25 @@ import { IptvService } from '../../services/iptv.service';
                   </label>
                 </div>
               </div>
+              <!-- Refresh Concurrency -->
+              <div class="grid grid-cols-1 md:grid-cols-3 gap-4 items-start">
+                <div>
+                  <label for="refreshConcurrency" class="text-gray-300">Parallel refreshes</label>
+                  <p class="text-xs text-gray-500 mt-1">How many sources are refreshed at the same time, overall and per provider server. Keep the per-server limit low if your provider caps connections.</p>
+                </div>
+                <div class="md:col-span-2 flex gap-4">
+                  <select id="refreshConcurrency" formControlName="refreshConcurrency" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
+                    @for (n of [1, 2, 3, 4, 6, 8]; track n) {
+                      <option [ngValue]="n">{{ n }} at once</option>
+                    }
+                  </select>
+                  <select id="refreshPerHost" formControlName="refreshPerHost" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
+                    @for (n of [1, 2, 3, 4]; track n) {
+                      <option [ngValue]="n">{{ n }} per server</option>
+                    }
+                  </select>
+                </div>
+              </div>
             </div>
           </section>
 
@@ -148,7 +168,7 @@ import { IptvService } from '../../services/iptv.service';
                 <div class="md:col-span-2">
                   <select id="epgTimeOffset" formControlName="epgTimeOffset" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
                     @for (opt of timeOffsetOptions; track opt.value) {
-                      <option [value]="opt.value">{{ opt.label }}</option>
+                      <option [ngValue]="opt.value">{{ opt.label }}</option>
                     }
                   </select>
                 </div>
@@ -161,13 +181,71 @@ import { IptvService } from '../../services/iptv.service';
                 </div>
                 <div class="md:col-span-2">
                   <select id="epgRefreshFrequency" formControlName="epgRefreshFrequency" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
-                    <option value="4">Every 4 hours</option>
-                    <option value="8">Every 8 hours</option>
-                    <option value="12">Every 12 hours</option>
-                    <option value="24">Every 24 hours</option>
+                    <option [ngValue]="4">Every 4 hours</option>
+                    <option [ngValue]="8">Every 8 hours</option>
+                    <option [ngValue]="12">Every 12 hours</option>
+                    <option [ngValue]="24">Every 24 hours</option>
+                  </select>
+                </div>
+              </div>
+              <!-- EPG Retention -->
+              <div class="grid grid-cols-1 md:grid-cols-3 gap-4 items-start">
+                <div>
+                  <label for="epgPastDays" class="text-gray-300">Guide Retention</label>
+                  <p class="text-xs text-gray-500 mt-1">Days of past programmes kept for catch-up, and days of upcoming programmes kept.</p>
+                </div>
+                <div class="md:col-span-2 flex gap-3">
+                  <select id="epgPastDays" formControlName="epgPastDays" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
+                    @for (n of [1, 3, 7, 14]; track n) {
+                      <option [ngValue]="n">{{ n }} {{ n === 1 ? 'day' : 'days' }} back</option>
+                    }
+                  </select>
+                  <select id="epgFutureDays" formControlName="epgFutureDays" class="w-full max-w-xs bg-gray-700 border border-gray-600 text-white text-sm rounded-lg focus:ring-sky-500 focus:border-sky-500 block p-2.5">
+                    @for (n of [1, 3, 7, 14]; track n) {
+                      <option [ngValue]="n">{{ n }} {{ n === 1 ? 'day' : 'days' }} ahead</option>
+                    }
                   </select>
                 </div>
               </div>
+              <!-- EPG Sources -->
+              <div class="grid grid-cols-1 md:grid-cols-3 gap-4 items-start">
+                <div>
+                  <label class="text-gray-300">Guide Sources</label>
+                  <p class="text-xs text-gray-500 mt-1">Extra XMLTV guides from a URL or a local file (.xml, .xml.gz or .xz). Sources are used in priority order, before the guides of your Xtream providers.</p>
+                </div>
+                <div class="md:col-span-2 space-y-3">
+                  @for (source of iptvService.epgSources(); track source.id) {
+                    <div class="flex items-center gap-3 bg-gray-800 rounded-lg p-3">
+                      <input type="checkbox" [checked]="source.isActive" (change)="onToggleEpgSource(source)" class="w-4 h-4 accent-sky-500" title="Enabled">
+                      <div class="flex-1 min-w-0">
+                        <p class="text-sm text-gray-200 truncate">{{ source.name }} <span class="text-xs text-gray-500">· {{ playlistName(source.playlistId) }}</span></p>
+                        <p class="text-xs text-gray-500 truncate" [title]="source.url">{{ source.url }}</p>
+                        @if (source.status === 'error') {
+                          <p class="text-xs text-red-400 truncate" [title]="source.errorMessage ?? ''">{{ source.errorMessage }}</p>
+                        }
+                      </div>
+                      <select [value]="source.timeOffset" (change)="onEpgSourceOffset(source, $event)" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg p-1.5" title="Time offset">
+                        @for (opt of timeOffsetOptions; track opt.value) {
+                          <option [value]="opt.value" [selected]="opt.value === source.timeOffset">{{ opt.label }}</option>
+                        }
+                      </select>
+                      <input type="number" [value]="source.priority" (change)="onEpgSourcePriority(source, $event)" class="w-16 bg-gray-700 border border-gray-600 text-white text-sm rounded-lg p-1.5" title="Priority (lower first)">
+                      <button type="button" (click)="onDeleteEpgSource(source)" class="px-3 py-1.5 rounded-lg text-sm font-semibold bg-gray-700 hover:bg-red-600">Delete</button>
+                    </div>
+                  }
+                  <div [formGroup]="epgSourceForm" class="flex flex-wrap gap-2">
+                    <input type="text" formControlName="name" placeholder="Name" class="flex-1 min-w-[8rem] bg-gray-700 border border-gray-600 text-white text-sm rounded-lg p-2.5">
+                    <input type="text" formControlName="url" placeholder="https://... or /path/to/guide.xml.gz" class="flex-[2] min-w-[14rem] bg-gray-700 border border-gray-600 text-white text-sm rounded-lg p-2.5">
+                    <select formControlName="playlistId" class="bg-gray-700 border border-gray-600 text-white text-sm rounded-lg p-2.5">
+                      <option [ngValue]="null">All playlists</option>
+                      @for (playlist of iptvService.playlists(); track playlist.id) {
+                        <option [ngValue]="playlist.id">{{ playlist.name }}</option>
+                      }
+                    </select>
+                    <button type="button" (click)="onAddEpgSource()" [disabled]="epgSourceForm.invalid" class="px-4 py-2 rounded-lg text-sm font-semibold bg-sky-600 text-white hover:bg-sky-500 disabled:opacity-50">Add</button>
+                  </div>
+                </div>
+              </div>
             </div>
           </section>
 
@@ -234,17 +312,33 @@ export class SettingsComponent {
     bufferSize: new FormControl<'off' | 'small' | 'medium' | 'large'>('medium', { nonNullable: true }),
     epgTimeOffset: new FormControl(0, { nonNullable: true }),
     epgRefreshFrequency: new FormControl(12, { nonNullable: true }),
+    refreshConcurrency: new FormControl(3, { nonNullable: true }),
+    refreshPerHost: new FormControl(1, { nonNullable: true }),
+    epgPastDays: new FormControl(7, { nonNullable: true }),
+    epgFutureDays: new FormControl(14, { nonNullable: true }),
+  });
+
+  epgSourceForm = new FormGroup({
+    name: new FormControl('', { nonNullable: true, validators: Validators.required }),
+    url: new FormControl('', { nonNullable: true, validators: Validators.required }),
+    playlistId: new FormControl<number | null>(null),
   });
 
   constructor() {
     this.generateTimeOffsetOptions();
+    this.iptvService.fetchEpgSources();
     // Populate form with initial values from the service
     const currentSettings = this.settingsService.getSettings();
     this.settingsForm.patchValue(currentSettings, { emitEvent: false });
 
     // Listen for form changes and update the service
-    this.settingsForm.valueChanges.pipe(debounceTime(300)).subscribe(values => {
-      this.settingsService.updateSettings(values);
+    this.settingsForm.valueChanges.pipe(debounceTime(300)).subscribe(async values => {
+      const current = this.settingsService.getSettings();
+      const guideChanged = (['epgTimeOffset', 'epgPastDays', 'epgFutureDays'] as const)
+        .some(key => values[key] !== undefined && values[key] !== current[key]);
+      await this.settingsService.updateSettings(values);
+      // The offset and retention are applied when the guide is imported, so the guide is fetched again
+      if (guideChanged) this.tauriService.invoke('refresh_epg');
     });
   }
 
@@ -265,7 +359,7 @@ export class SettingsComponent {
     const options: { value: number; label: string }[] = [];
     for (let minutes = -720; minutes <= 720; minutes += 30) {
       if (minutes === 0) {
-        options.push({ value: 0, label: 'None (UTC)' });
+        options.push({ value: 0, label: 'None' });
         continue;
       }
       const hours = Math.floor(Math.abs(minutes) / 60);
@@ -277,6 +371,43 @@ export class SettingsComponent {
     this.timeOffsetOptions = options;
   }
 
+  playlistName(playlistId: number | null | undefined): string {
+    if (playlistId == null) return 'All playlists';
+    return this.iptvService.playlists().find(p => p.id === playlistId)?.name ?? `Playlist ${playlistId}`;
+  }
+
+  async onAddEpgSource(): Promise<void> {
+    const { name, url, playlistId } = this.epgSourceForm.getRawValue();
+    const sources = this.iptvService.epgSources();
+    const priority = sources.length > 0 ? Math.max(...sources.map(s => s.priority)) + 1 : 0;
+    await this.iptvService.addEpgSource({ name: name.trim(), url: url.trim(), playlistId, priority });
+    this.epgSourceForm.reset();
+  }
+
+  onToggleEpgSource(source: EpgSource): void {
+    this.iptvService.updateEpgSource({ ...source, isActive: !source.isActive });
+  }
+
+  onEpgSourcePriority(source: EpgSource, event: Event): void {
+    const priority = Number((event.target as HTMLInputElement).value);
+    if (Number.isFinite(priority) && priority !== source.priority) {
+      this.iptvService.updateEpgSource({ ...source, priority });
+    }
+  }
+
+  onEpgSourceOffset(source: EpgSource, event: Event): void {
+    const timeOffset = Number((event.target as HTMLSelectElement).value);
+    if (Number.isFinite(timeOffset) && timeOffset !== source.timeOffset) {
+      this.iptvService.updateEpgSource({ ...source, timeOffset });
+    }
+  }
+
+  onDeleteEpgSource(source: EpgSource): void {
+    if (confirm(`Delete the guide source "${source.name}"?`)) {
+      this.iptvService.deleteEpgSource(source.id);
+    }
+  }
+
   async onClearImageCache(): Promise<void> {
     if (confirm('Are you sure you want to clear all cached images? This cannot be undone.')) {
         try {
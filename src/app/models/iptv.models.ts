@@ -12,6 +12,81 @@ export interface Playlist {
   maxConnections?: number;
   expirationDate?: string | null;
   lastUpdated?: string;
+  activeConnections?: number | null;
+  accountStatus?: string | null;
+  allowedOutputFormats?: string[];
+  serverTimezone?: string | null;
+  outputFormat?: 'ts' | 'm3u8' | null;
+  backupUrls?: string[];
+  activeUrl?: string | null;
+  urlFailures?: ServerUrlFailure[];
+  httpProfile?: HttpProfile;
+  errorCode?: 'auth' | 'network' | 'timeout' | 'provider' | 'parse' | 'internal' | 'cancelled' | null;
+}
+
+export interface HttpProfile {
+  userAgent?: string | null;
+  headers?: { name: string; value: string }[];
+  proxy?: string | null;
+  connectTimeoutSecs?: number | null;
+  readTimeoutSecs?: number | null;
+  acceptInvalidCerts?: boolean;
+}
+
+export interface ServerUrlFailure {
+  url: string;
+  error: string;
+  failedAt: string;
+}
+
+export interface ConnectionTestReport {
+  reachable: boolean;
+  authenticated: boolean;
+  error?: string | null;
+  accountStatus?: string | null;
+  expirationDate?: string | null;
+  maxConnections?: number | null;
+  activeConnections?: number | null;
+  categoryCount: number;
+  channelCount: number;
+  movieCount?: number | null;
+  seriesCount?: number | null;
+}
+
+export interface RefreshProgress {
+  playlistId: number;
+  catalogue: 'live' | 'movies' | 'series';
+  phase: 'account' | 'download' | 'parse' | 'insert' | 'done' | 'cancelled';
+  processed: number;
+  total?: number | null;
+}
+
+export interface PlaylistRefreshReport {
+  playlistId: number;
+  name: string;
+  outcome: 'success' | 'error' | 'cancelled' | 'skipped';
+  error?: string | null;
+  errorCode?: Playlist['errorCode'];
+  durationMs: number;
+  channels: number;
+  movies: number;
+  series: number;
+}
+
+export interface RefreshAllReport {
+  playlists: PlaylistRefreshReport[];
+  succeeded: number;
+  failed: number;
+  durationMs: number;
+}
+
+export interface ChannelChangeSummary {
+  playlistId: number;
+  added: string[];
+  removed: string[];
+  renamed: { id: number; oldName: string; newName: string }[];
+  updated: number;
+  unchanged: number;
 }
 
 export interface EpgEntry {
@@ -20,19 +95,56 @@ export interface EpgEntry {
   description?: string;
   startTime: string; // ISO string
   endTime: string; // ISO string
+  subTitle?: string | null;
+  categories?: string[];
+  icon?: string | null;
+  episodeNum?: string | null; // xmltv_ns, zero-based
+  episodeOnscreen?: string | null;
+  season?: number | null;
+  episode?: number | null;
+  episodeLabel?: string | null; // e.g. "S02E05"
+  rating?: string | null;
+  starRating?: string | null;
+  credits?: ProgrammeCredit[];
+  date?: string | null;
+  isNew?: boolean;
+  isPremiere?: boolean;
+  isPreviouslyShown?: boolean;
+}
+
+export interface ProgrammeCredit {
+  role: string; // director, actor, writer, presenter, ...
+  name: string;
+  character?: string | null;
+}
+
+export interface EpgSource {
+  id: number;
+  name: string;
+  url: string; // http(s) URL or local file path; .xml, .xml.gz and .xz are all accepted
+  playlistId?: number | null; // null applies the guide to every playlist
+  priority: number;
+  isActive: boolean;
+  status: 'active' | 'inactive' | 'error';
+  errorMessage?: string | null;
+  lastUpdated?: string | null;
+  timeOffset: number; // minutes added to every programme time of this guide
 }
 
 export interface Channel {
   id: number;
   playlistId: number;
   name: string;
-  logoUrl: string;
+  logoUrl: string | null;
   streamUrl: string;
   epg: EpgEntry[];
   category: string;
   categoryId: number | null;
   isFavorite: boolean;
   isHidden: boolean;
+  catchupDays?: number;
+  channelNumber?: number | null;
+  epgShift?: number; // minutes this channel's guide is moved by (tvg-shift)
 }
 
 export interface Category {
@@ -1,5 +1,5 @@
 import { Injectable, signal, computed, inject, WritableSignal } from '@angular/core';
-import { Channel, Playlist, Category, VODItem, Episode, Season, VODCategory, WatchHistory, PaginatedResponse } from '../models/iptv.models';
+import { Channel, Playlist, Category, VODItem, Episode, Season, VODCategory, WatchHistory, PaginatedResponse, ConnectionTestReport, ChannelChangeSummary, RefreshProgress, RefreshAllReport, EpgSource } from '../models/iptv.models';
 import { NotificationService } from './notification.service';
 import { SettingsService } from './settings.service';
 import { TauriService } from './tauri.service';
@@ -62,6 +62,14 @@ export class IptvService {
   private isFirstRefresh = true;
 
   readonly playlists = signal<Playlist[]>([]);
+  // Latest refresh diff per playlist id, from the `playlist-changes` event
+  readonly lastChannelChanges = signal<Record<number, ChannelChangeSummary>>({});
+  // Live progress of running refreshes per playlist id, from the `refresh-progress` event
+  readonly refreshProgress = signal<Record<number, RefreshProgress>>({});
+  // Per-playlist outcome of the last "refresh all" run, from the `refresh-all-report` event
+  readonly lastRefreshReport = signal<RefreshAllReport | null>(null);
+  // User-managed XMLTV guides, in priority order
+  readonly epgSources = signal<EpgSource[]>([]);
   readonly categories = signal<Category[]>([]);
   
   readonly watchHistory = signal<Record<string, WatchHistory>>({});
@@ -135,8 +143,8 @@ export class IptvService {
     return categories;
   });
 
-  readonly movieCategories = computed<VODCategory[]>(() => []);
-  readonly seriesCategories = computed<VODCategory[]>(() => []);
+  readonly movieCategories = signal<VODCategory[]>([]);
+  readonly seriesCategories = signal<VODCategory[]>([]);
 
   constructor() {
     this._listenForBackendEvents();
@@ -152,12 +160,52 @@ export class IptvService {
     return 'An unknown error occurred.';
   }
 
+  // The backend turns pasted Xtream API links (get.php / player_api.php) into Xtream playlists
+  private _notifyXtreamConversion(requestedType: Playlist['type'], saved: Playlist): void {
+    if (requestedType !== saved.type) {
+      this.notificationService.show(`"${saved.name}" was recognised as an Xtream link and saved as an Xtream playlist.`, 'info');
+    }
+  }
+
   private _listenForBackendEvents(): void {
     this.tauriService.listen<Playlist>('playlist-update', (event) => {
       const updatedPlaylist = event.payload;
       this.playlists.update(current =>
         current.map(p => p.id === updatedPlaylist.id ? updatedPlaylist : p)
       );
+      if (updatedPlaylist.status !== 'loading') {
+        this.refreshProgress.update(({ [updatedPlaylist.id]: _, ...rest }) => rest);
+      }
+    });
+
+    this.tauriService.listen<RefreshProgress>('refresh-progress', (event) => {
+      const progress = event.payload;
+      this.refreshProgress.update(current => {
+        const next = { ...current };
+        if (progress.phase === 'done' || progress.phase === 'cancelled') {
+          delete next[progress.playlistId];
+        } else {
+          next[progress.playlistId] = progress;
+        }
+        return next;
+      });
+    });
+
+    this.tauriService.listen<ChannelChangeSummary>('playlist-changes', (event) => {
+      const { playlistId, added, removed, renamed } = event.payload;
+      this.lastChannelChanges.update(current => ({ ...current, [playlistId]: event.payload }));
+      if (added.length || removed.length || renamed.length) {
+        const name = this.playlists().find(p => p.id === playlistId)?.name ?? 'Playlist';
+        this.notificationService.show(`${name}: ${added.length} added, ${removed.length} removed, ${renamed.length} renamed`, 'info');
+      }
+    });
+
+    this.tauriService.listen<RefreshAllReport>('refresh-all-report', (event) => {
+      this.lastRefreshReport.set(event.payload);
+      const failed = event.payload.playlists.filter(r => r.outcome === 'error');
+      if (failed.length) {
+        this.notificationService.show(`Refresh failed for ${failed.map(r => r.name).join(', ')}`, 'error');
+      }
     });
 
     this.tauriService.listen<void>('refresh-complete', async () => {
@@ -173,6 +221,8 @@ export class IptvService {
 
     this.tauriService.listen<void>('epg-complete', () => {
       console.log('EPG Refresh complete. Reloading current view...');
+      // Source statuses and errors are updated by the refresh
+      if (this.epgSources().length > 0) this.fetchEpgSources();
       if (this.lastFetchOptions && Object.keys(this.lastFetchOptions).length > 0) {
           this.fetchChannels(this.lastFetchOptions, false);
       }
@@ -250,7 +300,7 @@ export class IptvService {
     this.moviesLoading.set(true);
     this.moviesError.set(null);
     try {
-        const result = await this.tauriService.invoke<PaginatedResponse<VODItem>>('get_movies', { _options: options });
+        const result = await this.tauriService.invoke<PaginatedResponse<VODItem>>('get_movies', { options });
         if (loadMore) {
             this.movies.update(current => ({ ...result, items: [...current.items, ...result.items] }));
         } else {
@@ -270,7 +320,7 @@ export class IptvService {
     this.seriesLoading.set(true);
     this.seriesError.set(null);
     try {
-        const result = await this.tauriService.invoke<PaginatedResponse<VODItem>>('get_series', { _options: options });
+        const result = await this.tauriService.invoke<PaginatedResponse<VODItem>>('get_series', { options });
         if (loadMore) {
             this.series.update(current => ({ ...result, items: [...current.items, ...result.items] }));
         } else {
@@ -289,7 +339,7 @@ export class IptvService {
   async fetchSeasonsForSeries(seriesId: number) {
     this.seasonsLoading.set(true);
     try {
-        const seasons = await this.tauriService.invoke<Season[]>('get_seasons_for_series', { _series_id: seriesId });
+        const seasons = await this.tauriService.invoke<Season[]>('get_seasons_for_series', { seriesId });
         this.selectedSeriesSeasons.set(seasons);
     } catch (err) {
         console.error("Failed to fetch seasons:", err);
@@ -304,6 +354,10 @@ export class IptvService {
     try {
         const categories = await this.tauriService.invoke<Category[]>('get_categories');
         this.categories.set(categories);
+        const movieCategories = await this.tauriService.invoke<VODCategory[]>('get_vod_categories', { vodType: 'movie' });
+        this.movieCategories.set(movieCategories ?? []);
+        const seriesCategories = await this.tauriService.invoke<VODCategory[]>('get_vod_categories', { vodType: 'series' });
+        this.seriesCategories.set(seriesCategories ?? []);
     } catch (err) {
         console.error("Failed to fetch categories:", err);
         this.notificationService.show(`Error fetching categories: ${this._getErrorMessage(err)}`, 'error');
@@ -335,6 +389,7 @@ export class IptvService {
       const newPlaylist = await this.tauriService.invoke<Playlist>('add_playlist', { playlist_data: newPlaylistData });
       this.playlists.update(p => [...p, newPlaylist]);
       this.notificationService.show('Playlist added!', 'success');
+      this._notifyXtreamConversion(newPlaylistData.type, newPlaylist);
       this.refreshPlaylist(newPlaylist.id);
     } catch (err) {
       console.error("Failed to add playlist:", err);
@@ -342,11 +397,22 @@ export class IptvService {
     }
   }
 
+  async testPlaylistConnection(playlistData: Omit<Playlist, 'id' | 'isActive' | 'status'>): Promise<ConnectionTestReport | null> {
+    try {
+      return await this.tauriService.invoke<ConnectionTestReport>('test_playlist_connection', { playlistData });
+    } catch (err) {
+      console.error("Failed to test playlist connection:", err);
+      this.notificationService.show(`Error testing connection: ${this._getErrorMessage(err)}`, 'error');
+      return null;
+    }
+  }
+
   async updatePlaylist(playlist: Playlist) {
     try {
       const updatedPlaylist = await this.tauriService.invoke<Playlist>('update_playlist', { playlist });
       this.playlists.update(p => p.map(pl => pl.id === updatedPlaylist.id ? updatedPlaylist : pl));
       this.notificationService.show('Playlist updated!', 'success');
+      this._notifyXtreamConversion(playlist.type, updatedPlaylist);
     } catch (err) {
       console.error("Failed to update playlist:", err);
       this.notificationService.show(`Error updating playlist: ${this._getErrorMessage(err)}`, 'error');
@@ -376,6 +442,17 @@ export class IptvService {
     }
   }
 
+  async setPlaylistOutputFormat(id: number, outputFormat: 'ts' | 'm3u8' | null) {
+    try {
+        const updatedPlaylist = await this.tauriService.invoke<Playlist>('set_playlist_output_format', { id, outputFormat });
+        this.playlists.update(p => p.map(pl => pl.id === id ? updatedPlaylist : pl));
+        await this.fetchChannels(this.lastFetchOptions, false);
+    } catch (err) {
+        console.error("Failed to set playlist output format:", err);
+        this.notificationService.show(`Error updating output format: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
   async refreshPlaylist(id: number) { 
     this.notificationService.show('Refreshing playlist...', 'info'); 
     try {
@@ -386,6 +463,24 @@ export class IptvService {
     }
   }
   
+  async cancelRefresh(id: number) {
+    try {
+        await this.tauriService.invoke<boolean>('cancel_refresh', { playlistId: id });
+    } catch (err) {
+        console.error("Failed to cancel refresh:", err);
+        this.notificationService.show(`Error cancelling refresh: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
+  async cancelAllRefreshes() {
+    try {
+        await this.tauriService.invoke<number>('cancel_all_refreshes');
+    } catch (err) {
+        console.error("Failed to cancel refreshes:", err);
+        this.notificationService.show(`Error cancelling refreshes: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
   async refreshAllPlaylists(showNotification = true) { 
     if (showNotification) this.notificationService.show('Refreshing all playlists...', 'info'); 
     try {
@@ -396,6 +491,51 @@ export class IptvService {
     }
   }
 
+  async fetchEpgSources() {
+    try {
+      this.epgSources.set(await this.tauriService.invoke<EpgSource[]>('get_epg_sources'));
+    } catch (err) {
+      console.error("Failed to fetch EPG sources:", err);
+      this.notificationService.show(`Error loading EPG sources: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
+  async addEpgSource(source: { name: string; url: string; playlistId: number | null; priority: number; timeOffset?: number }) {
+    try {
+      const newSource = await this.tauriService.invoke<EpgSource>('add_epg_source', { source });
+      this.epgSources.update(s => [...s, newSource].sort((a, b) => a.priority - b.priority || a.id - b.id));
+      this.notificationService.show('EPG source added!', 'success');
+      this.tauriService.invoke('refresh_epg');
+    } catch (err) {
+      console.error("Failed to add EPG source:", err);
+      this.notificationService.show(`Error adding EPG source: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
+  async updateEpgSource(source: EpgSource) {
+    const previous = this.epgSources().find(es => es.id === source.id);
+    try {
+      const updatedSource = await this.tauriService.invoke<EpgSource>('update_epg_source', { source });
+      this.epgSources.update(s => s.map(es => es.id === updatedSource.id ? updatedSource : es).sort((a, b) => a.priority - b.priority || a.id - b.id));
+      // Offsets are applied when the guide is imported
+      if (previous && previous.timeOffset !== updatedSource.timeOffset) this.tauriService.invoke('refresh_epg');
+    } catch (err) {
+      console.error("Failed to update EPG source:", err);
+      this.notificationService.show(`Error updating EPG source: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
+  async deleteEpgSource(id: number) {
+    try {
+      await this.tauriService.invoke('delete_epg_source', { id });
+      this.epgSources.update(s => s.filter(es => es.id !== id));
+      this.notificationService.show('EPG source deleted.', 'success');
+    } catch (err) {
+      console.error("Failed to delete EPG source:", err);
+      this.notificationService.show(`Error deleting EPG source: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
   selectCategory(id: number | null) { this.selectedCategoryId.set(id); }
   toggleShowHiddenCategories() { this.showHiddenCategories.update(v => !v); }
   toggleShowHiddenChannels() { this.showHiddenChannels.update(v => !v); }
@@ -481,6 +621,19 @@ export class IptvService {
     }
   }
   
+  async setChannelEpgShift(id: number, minutes: number) {
+    try {
+      const updated = await this.tauriService.invoke<Channel>('set_channel_epg_shift', { id, minutes });
+      updateItemInPaginatedSignal(this.channels, updated);
+      updateItemInPaginatedSignal(this.favoriteChannels, updated);
+      updateItemInPaginatedSignal(this.recentChannels, updated);
+      if (this.selectedChannel()?.id === id) this.selectedChannel.set(updated);
+    } catch (err) {
+      console.error("Failed to set EPG shift:", err);
+      this.notificationService.show(`Error updating guide shift: ${this._getErrorMessage(err)}`, 'error');
+    }
+  }
+
   async addToRecentlyWatched(channel: Channel) { 
     try {
       await this.tauriService.invoke('add_to_recently_watched', { channelId: channel.id });
@@ -12,6 +12,10 @@ export interface AppSettings {
   bufferSize: 'off' | 'small' | 'medium' | 'large';
   epgTimeOffset: number; // in minutes
   epgRefreshFrequency: number; // in hours
+  refreshConcurrency: number; // playlists refreshed at once
+  refreshPerHost: number; // playlists refreshed at once against the same server
+  epgPastDays: number; // days of past programmes kept for catch-up
+  epgFutureDays: number; // days of upcoming programmes kept
 }
 
 @Injectable({
@@ -31,6 +35,10 @@ export class SettingsService {
     bufferSize: 'medium',
     epgTimeOffset: 0,
     epgRefreshFrequency: 12,
+    refreshConcurrency: 3,
+    refreshPerHost: 1,
+    epgPastDays: 7,
+    epgFutureDays: 14,
   };
 
   readonly settings = signal<AppSettings>(this.defaultSettings);
@@ -68,11 +76,9 @@ export class SettingsService {
     return this.settings();
   }
 
-  updateSettings(newSettings: Partial<AppSettings>): void {
-    this.settings.update(current => {
-      const updated = { ...current, ...newSettings };
-      this.saveSettings(updated);
-      return updated;
-    });
+  updateSettings(newSettings: Partial<AppSettings>): Promise<void> {
+    const updated = { ...this.settings(), ...newSettings };
+    this.settings.set(updated);
+    return this.saveSettings(updated);
   }
 }
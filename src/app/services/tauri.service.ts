@@ -39,11 +39,18 @@ export class TauriService {
   }
 
   /**
-   * Launches the MPV sidecar player with the given stream URL.
+   * Launches the MPV sidecar player with the given stream URL of a playlist.
    * Rust backend handles arguments like fullscreen (--fs).
    */
-  async playStream(url: string): Promise<void> {
-    return this.invoke('play_stream', { url });
+  async playStream(url: string, playlistId: number): Promise<void> {
+    return this.invoke('play_stream', { url, playlistId });
+  }
+
+  /**
+   * Plays the channel carrying the given provider number and returns it.
+   */
+  async tuneChannelByNumber<T>(number: number, playlistId?: number): Promise<T> {
+    return this.invoke<T>('tune_channel_by_number', { number, playlistId: playlistId ?? null });
   }
 
   async scheduleNotification(title: string, body: string, scheduleAt: number): Promise<void> {